@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use crate::manager::Manager;
+use crate::timeline::Timeline;
+
+pub fn gantt(timeline: &Timeline) -> String {
+    // one row per processor and column per tick, a run is its pid followed by `=`, idle is `.`
+    // and `^` below a row marks a preemption
+    let ticks = timeline.len() as i32;
+    let procs = timeline.processor_count() as u32;
+    let runs = timeline.runs();
+    let width = runs.iter().map(|r| r.pid.to_string().len()).max().unwrap_or(1);
+    let starts: HashSet<(u32, i32)> = runs.iter().map(|r| (r.processor, r.start)).collect();
+    let mut out = String::new();
+    // time axis every 5 ticks
+    let mut axis = String::new();
+    for tick in (0..ticks).step_by(5) {
+        let col = tick as usize * width;
+        if axis.len() < col {
+            axis.push_str(&" ".repeat(col - axis.len()));
+        }
+        axis.push_str(&tick.to_string());
+    }
+    writeln!(out, "{:<7}{}", "time", axis).unwrap();
+    for processor in 0..procs {
+        let mut row = String::new();
+        let mut marks = String::new();
+        for tick in 1..=ticks {
+            let cell = match timeline.pid_at(tick, processor) {
+                None => ".".repeat(width),
+                Some(pid) if starts.contains(&(processor, tick - 1)) => {
+                    format!("{:=<w$}", pid, w = width)
+                }
+                Some(_) => "=".repeat(width),
+            };
+            row.push_str(&cell);
+            let mark = if timeline.is_preemption(tick, processor) { "^" } else { " " };
+            marks.push_str(&format!("{:<w$}", mark, w = width));
+        }
+        writeln!(out, "{:<7}{}", format!("cpu {}", processor), row).unwrap();
+        if marks.contains('^') {
+            writeln!(out, "{:<7}{}", "", marks.trim_end()).unwrap();
+        }
+    }
+    out
+}
+
+pub fn queues(manager: &Manager) -> String {
+    // one line per task, running tasks first
+    let running = manager.get_running_task();
+    let mut out = String::new();
+    writeln!(out, "{:<18} {:>5} {:>8} {:>9} {:>6}", "queue", "pid", "priority", "remaining", "pages").unwrap();
+    for (name, tasks) in manager.queues() {
+        if tasks.is_empty() {
+            writeln!(out, "{:<18} {:>5}", name, "-").unwrap();
+        }
+        for task in tasks {
+            let task = task.borrow();
+            let name = match running.iter().position(|p| *p == Some(task.pid())) {
+                Some(cpu) if name == "running" => format!("running cpu {}", cpu),
+                _ => name.to_string(),
+            };
+            writeln!(out, "{:<18} {:>5} {:>8} {:>9} {:>6}",
+                     name, task.pid(), task.priority(), task.request_time(), task.memory_size()).unwrap();
+        }
+    }
+    out
+}
+
+pub fn memory_map(manager: &Manager, width: usize) -> String {
+    // a bar of `width` columns, each showing the owner of its first page, then one line per region
+    let pages = manager.memory_pages().max(1);
+    let mut regions: Vec<(String, u32, u32)> = manager.get_mem_usage().iter()
+        .flat_map(|(pid, holes)| holes.iter().map(move |h| (format!("pid {}", pid), h.to_tuple().0, h.to_tuple().1)))
+        .collect();
+    regions.extend(manager.get_shared_usage().iter()
+        .map(|(shm_id, region)| (format!("shm {}", shm_id), region.hole().to_tuple().0, region.hole().to_tuple().1)));
+    regions.sort_by_key(|(_, beg, _)| *beg);
+    // regions are lettered in address order
+    let letter = |i: usize| (b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz"[i % 52]) as char;
+    let mut bar: Vec<char> = (0..width)
+        .map(|col| {
+            let page = (col as u64 * pages as u64 / width.max(1) as u64) as u32;
+            regions.iter().position(|(_, beg, end)| *beg <= page && page < *end).map_or('.', letter)
+        })
+        .collect();
+    // regions smaller than a column still show up where they start, unless an earlier one did
+    let mut marked = HashSet::new();
+    for (i, (_, beg, _)) in regions.iter().enumerate() {
+        let col = (*beg as u64 * width as u64 / pages as u64) as usize;
+        if let Some(c) = bar.get_mut(col) {
+            if marked.insert(col) {
+                *c = letter(i);
+            }
+        }
+    }
+    let bar: String = bar.into_iter().collect();
+    let mut out = String::new();
+    writeln!(out, "|{}| {} pages", bar, pages).unwrap();
+    for (i, (owner, beg, end)) in regions.iter().enumerate() {
+        writeln!(out, "{} {:<8} [{}, {}) {} pages", letter(i), owner, beg, end, end - beg).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::size::MemSize;
+
+    fn manager() -> Manager {
+        // two processors, one task waits for pid 1 and arrives later
+        let mut manager = Manager::new();
+        manager.create_task(3, 1, MemSize::Pages(400), None);
+        manager.create_task(2, 1, MemSize::Pages(200), None);
+        manager.create_task(4, 3, MemSize::Pages(50), None);
+        manager.create_task(2, 1, MemSize::Pages(1200), Some(1));
+        for _ in 0..3 {
+            manager.advance();
+        }
+        manager
+    }
+
+    #[test]
+    fn gantt_golden() {
+        let mut timeline = Timeline::new();
+        for running in [[Some(1), None], [Some(1), Some(12)], [Some(3), Some(12)], [Some(3), None], [None, Some(1)], [Some(3), Some(1)]] {
+            timeline.push_tick(running.to_vec());
+        }
+        timeline.mark_preemption(3, 0);
+        let expected = "\
+time   0         5
+cpu 0  1===3===..3=
+           ^
+cpu 1  ..12==..1===
+";
+        assert_eq!(gantt(&timeline), expected);
+    }
+
+    #[test]
+    fn queues_golden() {
+        let expected = "\
+queue                pid priority remaining  pages
+running cpu 0          3        3         1     50
+running cpu 1          1        1         2    400
+new                    -
+ready                  -
+blocked                4        1         2   1200
+ready suspended        -
+blocked suspended      -
+";
+        assert_eq!(queues(&manager()), expected);
+    }
+
+    #[test]
+    fn memory_map_golden() {
+        // pid 2 has finished and left a hole
+        let expected = "\
+|ABBB.CCCCCCCCCC.................| 4096 pages
+A pid 3    [0, 50) 50 pages
+B pid 1    [50, 450) 400 pages
+C pid 4    [650, 1850) 1200 pages
+";
+        assert_eq!(memory_map(&manager(), 32), expected);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fs::File;
+use std::io::{self, Write, BufWriter};
+use serde::Serialize;
+use exp1::manager::Manager;
+use exp1::config::SimConfig;
+use exp1::workload::{Workload, WorkloadFormat};
+use exp1::generator::GeneratorConfig;
+use exp1::memory::MemoryMetrics;
+use exp1::memory::tlb::TlbStats;
+use exp1::memory::numa::NumaStats;
+use exp1::metrics::{TaskMetrics, AggregateMetrics};
+use exp1::chrome_trace::chrome_trace;
+use exp1::ascii;
+
+const USAGE: &str = "usage: exp1-batch generate [--count N] [--seed S] [--interarrival D] [--burst D] [--priority D] [--memory D] [--max-memory KIB] [--config FILE] [--out FILE]
+       exp1-batch [--config FILE] [--workload FILE] [--resume FILE] [--snapshot FILE] [--max-ticks N] [--metrics FILE|-] [--trace FILE|-] [--events FILE|-] [--chrome-trace FILE] [--ascii FILE|-] [--gantt FILE] [--memory-map FILE] [--verbose]";
+
+#[derive(Serialize)]
+struct Report {
+    ticks: i32,
+    // false if `max_ticks` ran out first
+    finished: bool,
+    tasks: usize,
+    scheduling: AggregateMetrics,
+    memory: MemoryMetrics,
+    tlb: TlbStats,
+    numa: NumaStats,
+    per_task: Vec<TaskMetrics>,
+}
+
+struct Options {
+    config: SimConfig,
+    workload: Workload,
+    // continue a saved run instead of starting the workload
+    resume: Option<String>,
+    // state written when the run stops
+    snapshot: Option<String>,
+    max_ticks: i32,
+    metrics: String,
+    trace: Option<String>,
+    events: Option<String>,
+    chrome_trace: Option<String>,
+    // text gantt chart, queues and memory map
+    ascii: Option<String>,
+    // figures written at the end, .svg or .png
+    gantt: Option<String>,
+    memory_map: Option<String>,
+    verbose: bool,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        config: SimConfig::default(),
+        workload: Workload::parse(include_str!("../default_workload.toml"), WorkloadFormat::Toml)?,
+        resume: None,
+        snapshot: None,
+        max_ticks: 100000,
+        metrics: String::from("-"),
+        trace: None,
+        events: None,
+        chrome_trace: None,
+        ascii: None,
+        gantt: None,
+        memory_map: None,
+        verbose: false,
+    };
+    // a resumed run keeps the config and tasks of its snapshot
+    let mut fresh_run_option = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--help" || args[i] == "-h" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        if args[i] == "--verbose" {
+            options.verbose = true;
+            i += 1;
+            continue;
+        }
+        let value = args.get(i + 1).ok_or(format!("{} requires a value", args[i]))?;
+        match args[i].as_str() {
+            "--config" => {
+                options.config = SimConfig::load(value)?;
+                fresh_run_option = Some("--config");
+            }
+            "--workload" => {
+                options.workload = Workload::load(value)?;
+                fresh_run_option = Some("--workload");
+            }
+            "--resume" => options.resume = Some(value.clone()),
+            "--snapshot" => options.snapshot = Some(value.clone()),
+            "--max-ticks" => options.max_ticks = value.parse().map_err(|_| format!("invalid tick count: {}", value))?,
+            "--metrics" => options.metrics = value.clone(),
+            "--trace" => options.trace = Some(value.clone()),
+            "--events" => options.events = Some(value.clone()),
+            "--chrome-trace" => options.chrome_trace = Some(value.clone()),
+            "--ascii" => options.ascii = Some(value.clone()),
+            "--gantt" => options.gantt = Some(value.clone()),
+            "--memory-map" => options.memory_map = Some(value.clone()),
+            _ => return Err(format!("unknown option: {}\n{}", args[i], USAGE)),
+        }
+        i += 2;
+    }
+    if let (Some(_), Some(option)) = (&options.resume, fresh_run_option) {
+        return Err(format!("--resume cannot be combined with {}, the snapshot has its own", option));
+    }
+    Ok(options)
+}
+
+fn generate_command(args: &[String]) -> Result<(), String> {
+    // exp1-batch generate [--count N] [--seed S] [--interarrival D] [--burst D] [--priority D] [--memory D]
+    //                     [--max-memory KIB] [--config FILE] [--out FILE]
+    let mut generator = GeneratorConfig::default();
+    let mut config = None;
+    let mut out = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--help" || args[i] == "-h" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        let value = args.get(i + 1).ok_or(format!("{} requires a value", args[i]))?;
+        match args[i].as_str() {
+            "--count" => generator.count = value.parse().map_err(|_| format!("invalid count: {}", value))?,
+            "--seed" => generator.seed = value.parse().map_err(|_| format!("invalid seed: {}", value))?,
+            "--interarrival" => generator.interarrival = value.parse()?,
+            "--burst" => generator.burst = value.parse()?,
+            "--priority" => generator.priority = value.parse()?,
+            "--memory" => generator.memory = value.parse()?,
+            "--max-memory" => generator.max_memory = value.parse().map_err(|_| format!("invalid memory size: {}", value))?,
+            "--config" => config = Some(SimConfig::load(value)?),
+            "--out" => out = Some(value),
+            _ => return Err(format!("unknown option: {}", args[i])),
+        }
+        i += 2;
+    }
+    // tasks have to fit in the memory of the config they run with
+    if let Some(config) = config {
+        generator.max_memory = generator.max_memory.min(config.memory_size / 1024).max(1);
+    }
+    let workload = generator.generate()?;
+    match out {
+        Some(path) => workload.save(path),
+        None => {
+            print!("{}", workload.to_text(WorkloadFormat::Toml));
+            Ok(())
+        }
+    }
+}
+
+fn open_output(path: &str) -> Result<Box<dyn Write>, String> {
+    // "-" is stdout
+    if path == "-" {
+        return Ok(Box::new(io::stdout()));
+    }
+    let file = File::create(path).map_err(|e| format!("unable to write {}: {}", path, e))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+fn run(options: Options) -> Result<bool, String> {
+    exp1::log::set_enabled(options.verbose);
+    let mut manager = match &options.resume {
+        None => Manager::with_config(options.config),
+        Some(path) => Manager::load_snapshot(path)?,
+    };
+    let write_error = |e: io::Error| format!("unable to write output: {}", e);
+    // one csv row per tick with the pid on every processor
+    let mut trace = match &options.trace {
+        None => None,
+        Some(path) => {
+            let mut out = open_output(path)?;
+            let procs: Vec<String> = (0..manager.get_running_task().len()).map(|i| format!("cpu{}", i)).collect();
+            writeln!(out, "time,{}", procs.join(",")).map_err(write_error)?;
+            Some(out)
+        }
+    };
+    // one json object per event and line
+    let mut events = match &options.events {
+        None => None,
+        Some(path) => Some(open_output(path)?),
+    };
+    // the chrome trace needs every event until the end
+    let mut history = vec![];
+    let receiver = if options.events.is_some() || options.chrome_trace.is_some() {
+        Some(manager.events())
+    } else {
+        None
+    };
+    if options.resume.is_none() {
+        manager.add_workload(&options.workload)?;
+    }
+    // a resumed run has no events before the snapshot
+    let start = manager.time();
+    while !manager.is_finished() && manager.time() < options.max_ticks {
+        manager.advance();
+        for event in receiver.iter().flat_map(|r| r.try_iter()) {
+            if let Some(out) = events.as_mut() {
+                writeln!(out, "{}", serde_json::to_string(&event).unwrap()).map_err(write_error)?;
+            }
+            if options.chrome_trace.is_some() {
+                history.push(event);
+            }
+        }
+        if let Some(out) = trace.as_mut() {
+            let pids: Vec<String> = manager.get_running_task().iter()
+                .map(|pid| pid.map_or(String::new(), |pid| pid.to_string()))
+                .collect();
+            writeln!(out, "{},{}", manager.time(), pids.join(",")).map_err(write_error)?;
+        }
+    }
+    if let Some(mut out) = trace {
+        out.flush().map_err(write_error)?;
+    }
+    if let Some(mut out) = events {
+        out.flush().map_err(write_error)?;
+    }
+    if let Some(path) = &options.chrome_trace {
+        let mut out = open_output(path)?;
+        let trace = chrome_trace(manager.timeline(), &history, start);
+        writeln!(out, "{}", serde_json::to_string(&trace).unwrap()).map_err(write_error)?;
+        out.flush().map_err(write_error)?;
+    }
+    if let Some(path) = &options.snapshot {
+        manager.save_snapshot(path)?;
+    }
+    if let Some(path) = &options.ascii {
+        let mut out = open_output(path)?;
+        write!(out, "{}\n{}\n{}", ascii::gantt(manager.timeline()), ascii::queues(&manager), ascii::memory_map(&manager, 64))
+            .map_err(write_error)?;
+        out.flush().map_err(write_error)?;
+    }
+    if let Some(path) = &options.gantt {
+        manager.export_gantt(path)?;
+    }
+    if let Some(path) = &options.memory_map {
+        manager.export_memory_map(path)?;
+    }
+    let report = Report {
+        ticks: manager.time(),
+        finished: manager.is_finished(),
+        tasks: manager.workload().tasks.len(),
+        scheduling: manager.aggregate_metrics(),
+        memory: manager.memory_metrics(),
+        tlb: manager.total_tlb_stats(),
+        numa: manager.numa_stats(),
+        per_task: manager.task_metrics(),
+    };
+    let mut out = open_output(&options.metrics)?;
+    writeln!(out, "{}", serde_json::to_string_pretty(&report).unwrap()).map_err(write_error)?;
+    out.flush().map_err(write_error)?;
+    Ok(report.finished)
+}
+
+fn main() {
+    // exit code 2 if the simulation did not finish within `--max-ticks`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // `generate` writes a synthetic workload instead of running one
+    if args.first().map(|a| a.as_str()) == Some("generate") {
+        if let Err(e) = generate_command(&args[1..]) {
+            eprintln!("[Error] {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    match parse_options(&args).and_then(run) {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(2),
+        Err(e) => {
+            eprintln!("[Error] {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::str::FromStr;
+use crate::events::{Event, Reason};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    // the run reaches a tick
+    Tick(i32),
+    // a task changes state
+    Pid(u32),
+    // any event with this reason, e.g. "preempted" or "page_fault"
+    Reason(String),
+}
+
+impl Breakpoint {
+    pub fn is_hit(&self, time: i32, events: &[Event]) -> bool {
+        // `events` are the ones of the tick that ended at `time`
+        match self {
+            Breakpoint::Tick(tick) => time == *tick,
+            Breakpoint::Pid(pid) => events.iter().any(|e| e.pid == Some(*pid) && e.is_transition()),
+            Breakpoint::Reason(name) => events.iter().any(|e| e.reason.name() == *name),
+        }
+    }
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        // comma separated, empty means none
+        s.split(',').map(str::trim).filter(|b| !b.is_empty()).map(str::parse).collect()
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // "tick:50", "pid:3" or a reason like "preempted"
+        let invalid = || format!("invalid breakpoint: {}", s);
+        match s.trim().split_once(':') {
+            Some(("tick", v)) => v.parse().map(Breakpoint::Tick).map_err(|_| invalid()),
+            Some(("pid", v)) => v.parse().map(Breakpoint::Pid).map_err(|_| invalid()),
+            Some(_) => Err(invalid()),
+            None if Reason::NAMES.contains(&s.trim()) => Ok(Breakpoint::Reason(s.trim().to_string())),
+            None => Err(format!("unknown event reason: {}, use one of {}", s.trim(), Reason::NAMES.join(", "))),
+        }
+    }
+}
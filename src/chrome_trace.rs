@@ -0,0 +1,75 @@
+use serde_json::{json, Value};
+use crate::events::{Event, Reason};
+use crate::timeline::Timeline;
+
+// trace viewers count in microseconds, a tick is shown as 1ms
+const TICK_US: i64 = 1000;
+// trace "processes" grouping the tracks
+const PROCESSORS: u32 = 0;
+const TASKS: u32 = 1;
+
+fn metadata(name: &str, pid: u32, tid: Option<u32>, value: String) -> Value {
+    let mut event = json!({ "ph": "M", "name": name, "pid": pid, "args": { "name": value } });
+    if let Some(tid) = tid {
+        event["tid"] = json!(tid);
+    }
+    event
+}
+
+fn is_covered_by_runs(reason: &Reason) -> bool {
+    // per-tick events are already in the duration slices
+    matches!(reason, Reason::Tick | Reason::Ran { .. } | Reason::Idle)
+}
+
+pub fn chrome_trace(timeline: &Timeline, events: &[Event], since: i32) -> Value {
+    // Chrome Trace Event Format, opens in chrome://tracing and Perfetto
+    // runs are cut at `since`, the time the events start from
+    let mut trace = vec![
+        metadata("process_name", PROCESSORS, None, String::from("processors")),
+        metadata("process_name", TASKS, None, String::from("tasks")),
+        metadata("thread_name", TASKS, Some(0), String::from("scheduler")),
+    ];
+    for processor in 0..timeline.processor_count() as u32 {
+        trace.push(metadata("thread_name", PROCESSORS, Some(processor), format!("cpu {}", processor)));
+    }
+    // one track per processor, one slice per run of a task
+    for run in timeline.runs().into_iter().filter(|r| r.end > since) {
+        let start = run.start.max(since);
+        trace.push(json!({
+            "ph": "X",
+            "name": format!("pid {}", run.pid),
+            "cat": "run",
+            "pid": PROCESSORS,
+            "tid": run.processor,
+            "ts": start as i64 * TICK_US,
+            "dur": (run.end - start) as i64 * TICK_US,
+            "args": { "pid": run.pid },
+        }));
+    }
+    // state transitions and memory events on one track per task
+    let mut tasks: Vec<u32> = vec![];
+    for event in events.iter().filter(|e| !is_covered_by_runs(&e.reason)) {
+        let args = serde_json::to_value(event).unwrap();
+        let name = event.reason.name();
+        let tid = event.pid.unwrap_or(0);
+        if event.pid.is_some() && !tasks.contains(&tid) {
+            tasks.push(tid);
+        }
+        // an event at time t happens as tick t begins, where a run starting at tick t is drawn
+        let ts = (event.time - 1).max(0) as i64 * TICK_US;
+        trace.push(json!({
+            "ph": "i",
+            "s": "t",
+            "name": name,
+            "cat": if event.is_transition() { "state" } else { "memory" },
+            "pid": TASKS,
+            "tid": tid,
+            "ts": ts,
+            "args": args,
+        }));
+    }
+    for pid in tasks {
+        trace.push(metadata("thread_name", TASKS, Some(pid), format!("pid {}", pid)));
+    }
+    json!({ "traceEvents": trace, "displayTimeUnit": "ms" })
+}
@@ -1,13 +1,14 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
-#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
 pub enum CondState {
+    #[default]
     NotDone,
     Done,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Condition {
     cond: CondState
 }
@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::memory::free_list::Placement;
+use crate::memory::frame::FramePolicy;
+use crate::memory::tlb::TlbPolicy;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimConfig {
+    pub time_slice: u32,
+    // multiprogramming slots
+    pub slots: u32,
+    pub processors: u32,
+    // memory size in bytes
+    pub memory_size: u64,
+    pub page_size: u32,
+    pub placement: Placement,
+    pub frames: u32,
+    pub frame_policy: FramePolicy,
+    pub tlb_size: usize,
+    pub tlb_ways: usize,
+    pub tlb_policy: TlbPolicy,
+    pub tlb_asid: bool,
+    // swap capacity in pages and transfer time per page
+    pub swap_capacity: u32,
+    pub swap_page_time: f64,
+    pub numa_nodes: u32,
+    pub numa_local_cost: u32,
+    pub numa_remote_cost: u32,
+    pub numa_aware: bool,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            time_slice: 4,
+            slots: 5,
+            processors: 2,
+            memory_size: 16777216,
+            page_size: 4096,
+            placement: Placement::FirstFit,
+            // 5 slots with 4-page working sets can ask for more than 16 frames, so thrashing shows up
+            frames: 16,
+            frame_policy: FramePolicy::WorkingSet { window: 4 },
+            tlb_size: 16,
+            tlb_ways: 4,
+            tlb_policy: TlbPolicy::Lru,
+            tlb_asid: false,
+            swap_capacity: 8192,
+            swap_page_time: 0.005,
+            numa_nodes: 1,
+            numa_local_cost: 1,
+            numa_remote_cost: 1,
+            numa_aware: false,
+        }
+    }
+}
+
+impl SimConfig {
+    pub fn from_toml(s: &str) -> Result<Self, String> {
+        // missing keys keep their default values
+        let config: SimConfig = toml::from_str(s).map_err(|e| format!("invalid config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+    pub fn to_toml(&self) -> String {
+        // going through a value puts the frame policy table after the plain keys
+        toml::to_string(&toml::Value::try_from(self).unwrap()).unwrap()
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let s = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("unable to read {}: {}", path.as_ref().display(), e))?;
+        Self::from_toml(&s)
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_toml())
+            .map_err(|e| format!("unable to write {}: {}", path.as_ref().display(), e))
+    }
+    pub fn memory_pages(&self) -> u32 {
+        (self.memory_size / self.page_size as u64) as u32
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        if self.time_slice == 0 {
+            return Err(String::from("time slice must be positive"));
+        }
+        if self.processors == 0 {
+            return Err(String::from("at least one processor is required"));
+        }
+        if self.slots == 0 {
+            return Err(String::from("at least one slot is required"));
+        }
+        if self.page_size == 0 {
+            return Err(String::from("page size must be positive"));
+        }
+        if self.memory_size < self.page_size as u64 {
+            return Err(String::from("memory must hold at least one page"));
+        }
+        if self.memory_size / self.page_size as u64 > u32::MAX as u64 {
+            return Err(String::from("memory has too many pages"));
+        }
+        if self.frames == 0 {
+            return Err(String::from("at least one frame is required"));
+        }
+        if self.tlb_size == 0 || self.tlb_ways == 0 {
+            return Err(String::from("tlb size and ways must be positive"));
+        }
+        // remote ticks are slowed down by remote / local, a free local access makes that endless
+        if self.numa_local_cost == 0 {
+            return Err(String::from("numa local cost must be positive"));
+        }
+        if self.swap_page_time < 0.0 || self.swap_page_time.is_nan() {
+            return Err(String::from("swap page time must not be negative"));
+        }
+        Ok(())
+    }
+}
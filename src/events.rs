@@ -0,0 +1,217 @@
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use serde::{Serialize, Deserialize};
+use crate::task::{Task, ProcessState};
+
+// process state including whether the task is swapped out
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    New,
+    Ready,
+    Running,
+    Blocked,
+    ReadySuspended,
+    BlockedSuspended,
+    Terminated,
+}
+
+impl TaskState {
+    pub fn of(task: &Task) -> Self {
+        match (task.state(), task.is_suspended()) {
+            (ProcessState::New, _) => TaskState::New,
+            (ProcessState::Ready, false) => TaskState::Ready,
+            (ProcessState::Ready, true) => TaskState::ReadySuspended,
+            (ProcessState::Blocked, false) => TaskState::Blocked,
+            (ProcessState::Blocked, true) => TaskState::BlockedSuspended,
+            (ProcessState::Running, _) => TaskState::Running,
+            (ProcessState::Terminated, _) => TaskState::Terminated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum Reason {
+    // a new tick starts
+    Tick,
+    Created,
+    Admitted,
+    Dispatched,
+    Preempted { by: u32 },
+    SliceExpired,
+    // waiting for another task or for memory
+    Blocked,
+    Unblocked,
+    Suspended { swapped_until: i32 },
+    Unsuspended { swapped_until: i32 },
+    SwapBusy,
+    SwapFull,
+    OutOfMemory,
+    Finished,
+    // terminated by a fault before its burst was done
+    Aborted,
+    // the task ran for one tick
+    Ran { remaining: i32 },
+    Idle,
+    TlbMiss { page: u32 },
+    PageFault { page: u32 },
+    SegmentationFault { segment: usize, offset: u32 },
+    Thrashing { demand: u32, frames: u32 },
+    Grew { pages: u32 },
+    Shrank { pages: u32 },
+    WaitingForMemory { pages: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub time: i32,
+    pub pid: Option<u32>,
+    pub processor: Option<u32>,
+    // both are the same for events that do not change the state
+    pub from: Option<TaskState>,
+    pub to: Option<TaskState>,
+    #[serde(flatten)]
+    pub reason: Reason,
+}
+
+impl Reason {
+    // every tag, in declaration order
+    pub const NAMES: [&'static str; 24] = [
+        "tick", "created", "admitted", "dispatched", "preempted", "slice_expired", "blocked", "unblocked",
+        "suspended", "unsuspended", "swap_busy", "swap_full", "out_of_memory", "finished", "aborted", "ran",
+        "idle", "tlb_miss", "page_fault", "segmentation_fault", "thrashing", "grew", "shrank", "waiting_for_memory",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        // the snake_case tag serde writes, e.g. "page_fault"
+        match self {
+            Reason::Tick => "tick",
+            Reason::Created => "created",
+            Reason::Admitted => "admitted",
+            Reason::Dispatched => "dispatched",
+            Reason::Preempted { .. } => "preempted",
+            Reason::SliceExpired => "slice_expired",
+            Reason::Blocked => "blocked",
+            Reason::Unblocked => "unblocked",
+            Reason::Suspended { .. } => "suspended",
+            Reason::Unsuspended { .. } => "unsuspended",
+            Reason::SwapBusy => "swap_busy",
+            Reason::SwapFull => "swap_full",
+            Reason::OutOfMemory => "out_of_memory",
+            Reason::Finished => "finished",
+            Reason::Aborted => "aborted",
+            Reason::Ran { .. } => "ran",
+            Reason::Idle => "idle",
+            Reason::TlbMiss { .. } => "tlb_miss",
+            Reason::PageFault { .. } => "page_fault",
+            Reason::SegmentationFault { .. } => "segmentation_fault",
+            Reason::Thrashing { .. } => "thrashing",
+            Reason::Grew { .. } => "grew",
+            Reason::Shrank { .. } => "shrank",
+            Reason::WaitingForMemory { .. } => "waiting_for_memory",
+        }
+    }
+}
+
+impl Event {
+    pub fn new(time: i32, reason: Reason) -> Self {
+        Event {
+            time,
+            pid: None,
+            processor: None,
+            from: None,
+            to: None,
+            reason,
+        }
+    }
+    pub fn task(mut self, pid: u32, from: Option<TaskState>, to: Option<TaskState>) -> Self {
+        self.pid = Some(pid);
+        self.from = from;
+        self.to = to;
+        self
+    }
+    pub fn on(mut self, processor: u32) -> Self {
+        self.processor = Some(processor);
+        self
+    }
+    pub fn is_transition(&self) -> bool {
+        self.from != self.to
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // [time] cpu pid from -> to reason
+        write!(f, "[{}]", self.time)?;
+        if let Some(processor) = self.processor {
+            write!(f, " cpu{}", processor)?;
+        }
+        if let Some(pid) = self.pid {
+            write!(f, " pid {}", pid)?;
+        }
+        if self.is_transition() {
+            let name = |s: Option<TaskState>| s.map_or(String::from("-"), |s| format!("{:?}", s));
+            write!(f, " {} -> {}", name(self.from), name(self.to))?;
+        }
+        write!(f, " {:?}", self.reason)
+    }
+}
+
+pub type Subscriber = Box<dyn FnMut(&Event)>;
+
+// hands every event to the subscribers, and prints it while logging is on
+#[derive(Default)]
+pub struct EventLog {
+    subscribers: Vec<Subscriber>,
+    // channels of `receiver`, dropped with their receiver
+    senders: Vec<Sender<Event>>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn subscribe<F>(&mut self, callback: F)
+        where F: FnMut(&Event) + 'static {
+        self.subscribers.push(Box::new(callback));
+    }
+    pub fn receiver(&mut self) -> Receiver<Event> {
+        // events are queued until read, e.g. with `try_iter`
+        let (sender, receiver) = channel();
+        self.senders.push(sender);
+        receiver
+    }
+    pub fn emit(&mut self, event: Event) {
+        if crate::log::enabled() {
+            println!("{}", event);
+        }
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber(&event);
+        }
+        self.senders.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_match_the_serde_tags() {
+        let reasons = [
+            Reason::Tick, Reason::Created, Reason::Admitted, Reason::Dispatched, Reason::Preempted { by: 1 },
+            Reason::SliceExpired, Reason::Blocked, Reason::Unblocked, Reason::Suspended { swapped_until: 1 },
+            Reason::Unsuspended { swapped_until: 1 }, Reason::SwapBusy, Reason::SwapFull, Reason::OutOfMemory,
+            Reason::Finished, Reason::Aborted, Reason::Ran { remaining: 1 }, Reason::Idle, Reason::TlbMiss { page: 1 },
+            Reason::PageFault { page: 1 }, Reason::SegmentationFault { segment: 0, offset: 1 },
+            Reason::Thrashing { demand: 1, frames: 1 }, Reason::Grew { pages: 1 }, Reason::Shrank { pages: 1 },
+            Reason::WaitingForMemory { pages: 1 },
+        ];
+        for (reason, name) in reasons.iter().zip(Reason::NAMES) {
+            assert_eq!(serde_json::to_value(reason).unwrap()["reason"], name);
+            assert_eq!(reason.name(), name);
+        }
+        assert_eq!(reasons.len(), Reason::NAMES.len());
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use resvg::{usvg, tiny_skia};
+use usvg::fontdb;
+use crate::manager::Manager;
+use crate::timeline::Timeline;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        // by extension, in any case
+        let extension = path.as_ref().extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("svg") => Ok(ImageFormat::Svg),
+            Some("png") => Ok(ImageFormat::Png),
+            _ => Err(format!("unknown image format, use .svg or .png: {}", path.as_ref().display())),
+        }
+    }
+}
+
+// make tasks colorful, the same pid always gets the same color
+pub fn pid_color(pid: u32) -> (f64, f64, f64, f64) {
+    let colors = [
+        (0.51, 0.67, 0.87, 0.8),
+        (0.51, 0.87, 0.67, 0.8),
+        (0.67, 0.51, 0.87, 0.8),
+        (0.67, 0.87, 0.51, 0.8),
+        (0.87, 0.67, 0.51, 0.8),
+        (0.87, 0.51, 0.67, 0.8),
+    ];
+    colors[((pid.max(1) - 1) % colors.len() as u32) as usize]
+}
+
+fn fill(color: (f64, f64, f64, f64)) -> String {
+    let (r, g, b, a) = color;
+    let c = |v: f64| (v * 255.0).round() as u8;
+    format!("fill=\"#{:02x}{:02x}{:02x}\" fill-opacity=\"{}\"", c(r), c(g), c(b), a)
+}
+
+const GREY: (f64, f64, f64, f64) = (0.3, 0.3, 0.3, 1.0);
+const STROKE: &str = "stroke=\"#4d4d4d\" stroke-width=\"1\"";
+
+fn svg_open(out: &mut String, width: f64, height: f64) {
+    // white background so png exports are not transparent
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+                   font-family=\"sans-serif\" font-size=\"11\">", w = width, h = height).unwrap();
+    writeln!(out, "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", width, height).unwrap();
+}
+
+fn text(out: &mut String, x: f64, y: f64, s: &str) {
+    writeln!(out, "<text x=\"{}\" y=\"{}\" {}>{}</text>", x, y, fill(GREY), s).unwrap();
+}
+
+pub fn gantt_svg(timeline: &Timeline) -> String {
+    // same layout as the window, ticks get narrower for long runs
+    let ticks = timeline.len() as i32;
+    let procs = timeline.processor_count();
+    let (gx, gy, rh) = (50.0, 10.0, 20.0);
+    let tw = (1200.0 / ticks.max(1) as f64).clamp(2.0, 12.0);
+    let tx = |tick: i32| gx + tw * tick as f64;
+    let row_y = |p: usize| gy + (rh + 4.0) * p as f64;
+    let mut out = String::new();
+    svg_open(&mut out, tx(ticks) + 20.0, row_y(procs) + 20.0);
+    for p in 0..procs {
+        text(&mut out, 5.0, row_y(p) + 14.0, &format!("cpu {}", p));
+        writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#e6e6e6\"/>",
+                 gx, row_y(p), tw * ticks as f64, rh).unwrap();
+    }
+    let runs = timeline.runs();
+    for run in runs.iter() {
+        let (x, y, w) = (tx(run.start), row_y(run.processor as usize), tw * (run.end - run.start) as f64);
+        writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {} {}/>",
+                 x, y, w, rh, fill(pid_color(run.pid)), STROKE).unwrap();
+        if w >= 20.0 {
+            text(&mut out, x + 3.0, y + 14.0, &run.pid.to_string());
+        }
+    }
+    // context switches in black, preemptions in red
+    for run in runs.iter() {
+        let color = if run.preempted {
+            "#e61a1a"
+        } else if run.context_switch {
+            "#1a1a1a"
+        } else {
+            continue;
+        };
+        let (x, y) = (tx(run.start), row_y(run.processor as usize));
+        writeln!(out, "<line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\"/>",
+                 y - 2.0, y + rh + 2.0, color, x = x).unwrap();
+    }
+    // time axis, labels at least 30px apart
+    let ay = row_y(procs) + 2.0;
+    let step = ((30.0 / (tw * 5.0)).ceil() as usize).max(1) * 5;
+    for tick in (0..=ticks).step_by(step) {
+        writeln!(out, "<line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" {}/>", ay - 4.0, ay, STROKE, x = tx(tick)).unwrap();
+        text(&mut out, tx(tick) - 3.0, ay + 11.0, &tick.to_string());
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+pub fn memory_svg(manager: &Manager) -> String {
+    // memory bar like the window, labelled with pids and shared regions
+    let (bx, by, w, h) = (30.0, 30.0, 140.0, 340.0);
+    let pages = manager.memory_pages().max(1) as f64;
+    let cth = |pos: u32| pos as f64 / pages * h;
+    let mut out = String::new();
+    svg_open(&mut out, bx + w + 120.0, by + h + 20.0);
+    text(&mut out, bx, by - 12.0, &format!("memory at time {}", manager.time()));
+    let mut usage: Vec<_> = manager.get_mem_usage().iter()
+        .flat_map(|(pid, holes)| holes.iter().map(move |h| (*pid, *h)))
+        .collect();
+    usage.sort_by_key(|(_, hole)| hole.to_tuple());
+    for (pid, hole) in usage {
+        let (beg, _) = hole.to_tuple();
+        let (y, hh) = (by + cth(beg), cth(hole.get_size()));
+        writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {} {}/>",
+                 bx, y, w, hh, fill(pid_color(pid)), STROKE).unwrap();
+        text(&mut out, bx + w + 5.0, y + hh / 2.0 + 4.0, &format!("pid: {}", pid));
+    }
+    // shared regions are grey
+    let mut shared: Vec<_> = manager.get_shared_usage().iter().collect();
+    shared.sort_by_key(|(shm_id, _)| **shm_id);
+    for (shm_id, region) in shared {
+        let (beg, _) = region.hole().to_tuple();
+        let (y, hh) = (by + cth(beg), cth(region.hole().get_size()));
+        writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {} {}/>",
+                 bx, y, w, hh, fill((0.7, 0.7, 0.7, 0.8)), STROKE).unwrap();
+        text(&mut out, bx + w + 5.0, y + hh / 2.0 + 4.0, &format!("shm: {} ({})", shm_id, region.users().len()));
+    }
+    // numa node boundaries
+    let nodes = manager.numa().nodes();
+    if nodes.len() > 1 {
+        for (i, node) in nodes.iter().enumerate() {
+            let (beg, _) = node.range();
+            text(&mut out, bx - 25.0, by + cth(beg) + 12.0, &format!("N{}", i));
+            writeln!(out, "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" {}/>", bx, bx + w, STROKE, y = by + cth(beg)).unwrap();
+        }
+    }
+    writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" {}/>", bx, by, w, h, STROKE).unwrap();
+    out.push_str("</svg>\n");
+    out
+}
+
+pub fn svg_to_png(svg: &str) -> Result<Vec<u8>, String> {
+    // text is drawn with the system fonts
+    let mut options = usvg::Options::default();
+    let fonts = options.fontdb_mut();
+    fonts.load_system_fonts();
+    // sans-serif means Arial to fontdb, fall back to any installed font
+    let query = fontdb::Query { families: &[fontdb::Family::SansSerif], ..Default::default() };
+    if fonts.query(&query).is_none() {
+        let names: Vec<String> = fonts.faces().filter_map(|f| f.families.first()).map(|(name, _)| name.clone()).collect();
+        let family = names.iter().find(|name| name.contains("Sans")).or(names.first()).cloned();
+        if let Some(family) = family {
+            fonts.set_sans_serif_family(family);
+        }
+    }
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| format!("invalid svg: {}", e))?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or(format!("image too large: {}x{}", size.width(), size.height()))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| format!("unable to encode png: {}", e))
+}
+
+pub fn save<P: AsRef<Path>>(svg: &str, path: P) -> Result<(), String> {
+    // svg or png by extension
+    let path = path.as_ref();
+    let data = match ImageFormat::from_path(path)? {
+        ImageFormat::Svg => svg.as_bytes().to_vec(),
+        ImageFormat::Png => svg_to_png(svg)?,
+    };
+    fs::write(path, data).map_err(|e| format!("unable to write {}: {}", path.display(), e))
+}
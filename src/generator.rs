@@ -0,0 +1,131 @@
+use std::str::FromStr;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
+use crate::memory::size::MemSize;
+use crate::workload::{Workload, TaskSpec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Distribution {
+    Constant { value: f64 },
+    Uniform { min: f64, max: f64 },
+    Exponential { mean: f64 },
+    // heavy tail, values start at `scale`
+    Pareto { scale: f64, shape: f64 },
+    // `high` with probability `p_high`, `low` otherwise
+    Bimodal { low: f64, high: f64, p_high: f64 },
+}
+
+impl Distribution {
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        // inverse transform sampling on a uniform in [0, 1)
+        let u: f64 = rng.gen();
+        match *self {
+            Distribution::Constant { value } => value,
+            Distribution::Uniform { min, max } => min + (max - min) * u,
+            Distribution::Exponential { mean } => -mean * (1.0 - u).ln(),
+            Distribution::Pareto { scale, shape } => scale / (1.0 - u).powf(1.0 / shape),
+            Distribution::Bimodal { low, high, p_high } => if u < p_high { high } else { low },
+        }
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        let ok = match *self {
+            Distribution::Constant { value } => value.is_finite(),
+            Distribution::Uniform { min, max } => min.is_finite() && max.is_finite() && min <= max,
+            Distribution::Exponential { mean } => mean.is_finite() && mean > 0.0,
+            Distribution::Pareto { scale, shape } => scale.is_finite() && scale > 0.0 && shape.is_finite() && shape > 0.0,
+            Distribution::Bimodal { low, high, p_high } => {
+                low.is_finite() && high.is_finite() && (0.0..=1.0).contains(&p_high)
+            }
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("invalid distribution parameters: {:?}", self))
+        }
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // "5", "uniform:1:10", "exp:4", "pareto:1:2.5" or "bimodal:2:20:0.2"
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        let args: Vec<f64> = parts[1..].iter()
+            .map(|p| p.parse().map_err(|_| format!("invalid number in distribution: {}", s)))
+            .collect::<Result<_, _>>()?;
+        let dist = match (parts[0], args.as_slice()) {
+            ("const", [value]) => Distribution::Constant { value: *value },
+            ("uniform", [min, max]) => Distribution::Uniform { min: *min, max: *max },
+            ("exp", [mean]) => Distribution::Exponential { mean: *mean },
+            ("pareto", [scale, shape]) => Distribution::Pareto { scale: *scale, shape: *shape },
+            ("bimodal", [low, high, p_high]) => Distribution::Bimodal { low: *low, high: *high, p_high: *p_high },
+            (value, []) => Distribution::Constant {
+                value: value.parse().map_err(|_| format!("unknown distribution: {}", s))?,
+            },
+            _ => return Err(format!("unknown distribution: {}", s)),
+        };
+        dist.validate()?;
+        Ok(dist)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorConfig {
+    pub count: u32,
+    pub seed: u64,
+    // ticks between two arrivals
+    pub interarrival: Distribution,
+    pub burst: Distribution,
+    pub priority: Distribution,
+    // memory in KiB, at most `max_memory`
+    pub memory: Distribution,
+    pub max_memory: u64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            count: 20,
+            seed: 0,
+            interarrival: Distribution::Exponential { mean: 2.0 },
+            burst: Distribution::Exponential { mean: 4.0 },
+            priority: Distribution::Uniform { min: 1.0, max: 5.0 },
+            memory: Distribution::Pareto { scale: 64.0, shape: 1.5 },
+            // the default memory size
+            max_memory: 16384,
+        }
+    }
+}
+
+impl GeneratorConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for dist in [&self.interarrival, &self.burst, &self.priority, &self.memory] {
+            dist.validate()?;
+        }
+        if self.max_memory == 0 {
+            return Err(String::from("max memory must be positive"));
+        }
+        Ok(())
+    }
+    pub fn generate(&self) -> Result<Workload, String> {
+        // the same config and seed always give the same workload
+        self.validate()?;
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut arrival = 0.0;
+        let mut tasks = vec![];
+        for id in 1..=self.count {
+            if id > 1 {
+                arrival += self.interarrival.sample(&mut rng).max(0.0);
+            }
+            let burst = self.burst.sample(&mut rng).round().max(1.0) as i32;
+            let priority = self.priority.sample(&mut rng).round() as i32;
+            let memory = self.memory.sample(&mut rng).round().clamp(1.0, self.max_memory as f64) as u64;
+            tasks.push(TaskSpec::new(id, arrival as i32, burst, priority, MemSize::kib(memory), None));
+        }
+        Ok(Workload { tasks })
+    }
+}
@@ -0,0 +1,68 @@
+use crate::manager::{Manager, Snapshot};
+use crate::scheduler::{RunRecord, RecordLengths};
+
+// snapshots every `interval` ticks, earlier ticks are replayed from the closest one
+pub struct History {
+    interval: i32,
+    // (time, snapshot, how much of `record` it had) in time order
+    snapshots: Vec<(i32, Snapshot, RecordLengths)>,
+    // the timeline, memory history and finished tasks up to `latest`, kept once for all snapshots
+    record: RunRecord,
+    // the furthest tick the run has reached
+    latest: i32,
+}
+
+impl History {
+    pub fn new(manager: &Manager, interval: i32) -> Self {
+        let mut history = History {
+            interval: interval.max(1),
+            snapshots: vec![],
+            record: RunRecord::default(),
+            latest: manager.time(),
+        };
+        history.checkpoint(manager);
+        history
+    }
+    pub fn latest(&self) -> i32 {
+        self.latest
+    }
+    pub fn earliest(&self) -> i32 {
+        self.snapshots.first().map_or(0, |(time, _, _)| *time)
+    }
+    pub fn record(&mut self, manager: &Manager) {
+        // call after every tick
+        let time = manager.time();
+        if time > self.latest {
+            self.record.append(manager.record_since(self.record.lengths()));
+            self.latest = time;
+        }
+        if time % self.interval == 0 && !self.snapshots.iter().any(|(t, _, _)| *t == time) {
+            let pos = self.snapshots.partition_point(|(t, _, _)| *t < time);
+            self.snapshots.insert(pos, (time, manager.snapshot_without_record(), manager.record_lengths()));
+        }
+    }
+    pub fn checkpoint(&mut self, manager: &Manager) {
+        // the run was changed by hand, what was recorded after now no longer happens
+        let time = manager.time();
+        let lengths = manager.record_lengths();
+        self.snapshots.retain(|(t, _, _)| *t < time);
+        self.record.truncate(lengths);
+        self.record.append(manager.record_since(self.record.lengths()));
+        self.snapshots.push((time, manager.snapshot_without_record(), lengths));
+        self.latest = time;
+    }
+    pub fn seek(&self, tick: i32) -> Result<Manager, String> {
+        // replay from the last snapshot at or before `tick`
+        let tick = tick.clamp(self.earliest(), self.latest);
+        let (_, snapshot, lengths) = self.snapshots.iter().rev()
+            .find(|(t, _, _)| *t <= tick)
+            .ok_or(format!("no snapshot before tick {}", tick))?;
+        let mut snapshot = snapshot.clone();
+        snapshot.set_record(self.record.prefix(*lengths));
+        let mut manager = Manager::from_snapshot(snapshot)?;
+        while manager.time() < tick {
+            manager.advance();
+        }
+        Ok(manager)
+    }
+}
@@ -0,0 +1,25 @@
+// simulator core, the GTK window in main.rs is behind the `gui` feature
+pub mod log;
+pub mod task;
+pub mod scheduler;
+pub mod memory;
+pub mod processor;
+pub mod manager;
+pub mod condition;
+pub mod config;
+pub mod workload;
+pub mod generator;
+pub mod metrics;
+pub mod events;
+pub mod timeline;
+pub mod export;
+pub mod chrome_trace;
+pub mod ascii;
+pub mod history;
+pub mod breakpoint;
+
+pub use manager::Manager;
+pub use config::SimConfig;
+pub use workload::{Workload, TaskSpec, WorkloadFormat};
+pub use generator::{GeneratorConfig, Distribution};
+pub use memory::size::MemSize;
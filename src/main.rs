@@ -1,28 +1,143 @@
-mod task;
-mod scheduler;
-mod memory;
-mod processor;
-mod manager;
-mod condition;
-
 use std::rc::Rc;
-use std::cell::RefCell;
-use crate::manager::*;
+use std::cell::{Cell, RefCell};
+use exp1::manager::*;
+use exp1::memory::size::MemSize;
+use exp1::memory::free_list::Placement;
+use exp1::config::SimConfig;
+use exp1::workload::{Workload, WorkloadFormat};
+use exp1::export::pid_color;
+use exp1::history::History;
+use exp1::breakpoint::Breakpoint;
+use exp1::events::Event;
 use gtk::prelude::BuilderExtManual;
 use gtk::{WidgetExt, Inhibit, ButtonExt, Application, GtkWindowExt, EntryExt, LabelExt};
+use gtk::{DialogExt, SpinButtonExt, ComboBoxExt, ToggleButtonExt, FileChooserExt, FileChooserButtonExt, ResponseType};
+use gtk::{FileChooserAction, FileChooserDialog, ScrolledWindowExt, AdjustmentExt};
 use gio::ApplicationExt;
 use gio::prelude::ApplicationExtManual;
 
+// ticks between two snapshots kept for going back in time
+const HISTORY_INTERVAL: i32 = 10;
+
+fn show_time(adjustment: &gtk::Adjustment, history: &History, time: i32) {
+    // the scrubber covers every tick reached so far
+    adjustment.set_lower(history.earliest() as f64);
+    adjustment.set_upper(history.latest() as f64);
+    adjustment.set_value(time as f64);
+}
+
+fn default_workload() -> Workload {
+    Workload::parse(include_str!("default_workload.toml"), WorkloadFormat::Toml).unwrap()
+}
+
+fn choose_file(parent: &gtk::Window, title: &str, action: FileChooserAction, name: &str) -> Option<std::path::PathBuf> {
+    // run a modal file dialog, `name` is suggested when saving
+    let accept = if let FileChooserAction::Save = action { "_Save" } else { "_Open" };
+    let dialog = FileChooserDialog::with_buttons(Some(title), Some(parent), action,
+                                                 &[("_Cancel", ResponseType::Cancel), (accept, ResponseType::Accept)]);
+    dialog.set_do_overwrite_confirmation(true);
+    if let FileChooserAction::Save = action {
+        dialog.set_current_name(name);
+    }
+    let path = if dialog.run() == ResponseType::Accept { dialog.get_filename() } else { None };
+    dialog.close();
+    path
+}
+
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a String>, String> {
+    // value following `flag`, if the flag is given
+    match args.iter().position(|a| a == flag) {
+        None => Ok(None),
+        Some(i) => args.get(i + 1).map(Some).ok_or(format!("{} requires a file", flag)),
+    }
+}
+
+#[derive(Clone)]
+struct SettingsWidgets {
+    time_slice: gtk::SpinButton,
+    slots: gtk::SpinButton,
+    processors: gtk::SpinButton,
+    memory: gtk::SpinButton,
+    page_size: gtk::SpinButton,
+    frames: gtk::SpinButton,
+    numa_nodes: gtk::SpinButton,
+    placement: gtk::ComboBoxText,
+    numa_aware: gtk::CheckButton,
+}
+
+impl SettingsWidgets {
+    fn new(builder: &gtk::Builder) -> Self {
+        SettingsWidgets {
+            time_slice: builder.get_object("time_slice_spin").unwrap(),
+            slots: builder.get_object("slots_spin").unwrap(),
+            processors: builder.get_object("processors_spin").unwrap(),
+            memory: builder.get_object("memory_spin").unwrap(),
+            page_size: builder.get_object("page_size_spin").unwrap(),
+            frames: builder.get_object("frames_spin").unwrap(),
+            numa_nodes: builder.get_object("numa_nodes_spin").unwrap(),
+            placement: builder.get_object("placement_combo").unwrap(),
+            numa_aware: builder.get_object("numa_aware_check").unwrap(),
+        }
+    }
+    fn fill(&self, config: &SimConfig) {
+        self.time_slice.set_value(config.time_slice as f64);
+        self.slots.set_value(config.slots as f64);
+        self.processors.set_value(config.processors as f64);
+        self.memory.set_value((config.memory_size / 1048576) as f64);
+        self.page_size.set_value(config.page_size as f64);
+        self.frames.set_value(config.frames as f64);
+        self.numa_nodes.set_value(config.numa_nodes as f64);
+        self.placement.set_active_id(Some(match config.placement {
+            Placement::BestFit => "best_fit",
+            Placement::FirstFit => "first_fit",
+        }));
+        self.numa_aware.set_active(config.numa_aware);
+    }
+    fn read(&self, base: &SimConfig) -> SimConfig {
+        // settings without a widget are kept from `base`
+        let mut config = base.clone();
+        config.time_slice = self.time_slice.get_value_as_int() as u32;
+        config.slots = self.slots.get_value_as_int() as u32;
+        config.processors = self.processors.get_value_as_int() as u32;
+        // a size that is not whole MiB is kept unless it was edited
+        let memory = self.memory.get_value_as_int() as u64 * 1048576;
+        if memory / 1048576 != base.memory_size / 1048576 {
+            config.memory_size = memory;
+        }
+        config.page_size = self.page_size.get_value_as_int() as u32;
+        config.frames = self.frames.get_value_as_int() as u32;
+        config.numa_nodes = self.numa_nodes.get_value_as_int() as u32;
+        config.placement = match self.placement.get_active_id().as_ref().map(|id| id.as_str()) {
+            Some("best_fit") => Placement::BestFit,
+            _ => Placement::FirstFit,
+        };
+        config.numa_aware = self.numa_aware.get_active();
+        config
+    }
+}
+
 fn main() {
-    let mut manager = Manager::new();
-    manager.create_task(3, 1, 400, None);
-    manager.create_task(2, 1, 200, None);
-    manager.create_task(4, 1, 50, None);
-    manager.create_task( 3, 1, 1200, Some(3));
-    manager.create_task(5, 1, 1200, None);
-    manager.create_task(2, 1, 2000, None);
-    manager.create_task(6, 1, 20, None);
+    // `--config <file>` loads the simulation settings from a TOML file
+    // `--workload <file>` replaces the default tasks with a TOML or JSON workload
+    let args: Vec<String> = std::env::args().collect();
+    // the window prints every event like the scheduler always did
+    exp1::log::set_enabled(true);
+    let loaded = arg_value(&args, "--config").and_then(|path| {
+        let config = path.map_or(Ok(SimConfig::default()), SimConfig::load)?;
+        let workload = arg_value(&args, "--workload")?.map_or(Ok(default_workload()), Workload::load)?;
+        Ok((config, workload))
+    });
+    let (config, workload) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("[Error] {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut manager = Manager::with_config(config);
+    manager.add_workload(&workload).unwrap();
 
+    let history = Rc::new(RefCell::new(History::new(&manager, HISTORY_INTERVAL)));
     let manager = Rc::new(RefCell::new(manager));
 
     let application = Application::new(
@@ -37,14 +152,157 @@ fn main() {
         window.set_application(Some(app));
 
         let draw_area: gtk::DrawingArea = builder.get_object("draw_area").unwrap();
+        let chart_area: gtk::DrawingArea = builder.get_object("chart_area").unwrap();
+        let gantt_area: gtk::DrawingArea = builder.get_object("gantt_area").unwrap();
+        let gantt_scroll: gtk::ScrolledWindow = builder.get_object("gantt_scroll").unwrap();
         let exec_button: gtk::Button = builder.get_object("exec_button").unwrap();
         let next_button: gtk::Button = builder.get_object("next_button").unwrap();
+        let prev_button: gtk::Button = builder.get_object("prev_button").unwrap();
+        let time_adjustment: gtk::Adjustment = builder.get_object("time_adjustment").unwrap();
+        let play_button: gtk::ToggleButton = builder.get_object("play_button").unwrap();
+        let speed_adjustment: gtk::Adjustment = builder.get_object("speed_adjustment").unwrap();
+        let breakpoint_entry: gtk::Entry = builder.get_object("breakpoint_entry").unwrap();
+        // events of the last tick run with next, for breakpoints
+        let last_events: Rc<RefCell<Vec<Event>>> = Rc::new(RefCell::new(vec![]));
         let entry: gtk::Entry = builder.get_object("command_entry").unwrap();
         let msg_label: gtk::Label = builder.get_object("msg_label").unwrap();
         let time_label: gtk::Label = builder.get_object("time_label").unwrap();
+        let settings_button: gtk::Button = builder.get_object("settings_button").unwrap();
+        let open_button: gtk::Button = builder.get_object("open_button").unwrap();
+        let save_button: gtk::Button = builder.get_object("save_button").unwrap();
+        let export_gantt_button: gtk::Button = builder.get_object("export_gantt_button").unwrap();
+        let export_memory_button: gtk::Button = builder.get_object("export_memory_button").unwrap();
+        let settings_dialog: gtk::Dialog = builder.get_object("settings_dialog").unwrap();
+        let config_file: gtk::FileChooserButton = builder.get_object("config_file").unwrap();
+        let settings = SettingsWidgets::new(&builder);
+        // settings edited in the dialog, starting from the running or a loaded config
+        let base_config = Rc::new(RefCell::new(SimConfig::default()));
+
+        let settings_clone = settings.clone();
+        let base_config_clone = base_config.clone();
+        let msg_label_clone = msg_label.clone();
+        config_file.connect_file_set(move |chooser| {
+            if let Some(path) = chooser.get_filename() {
+                match SimConfig::load(path) {
+                    Ok(config) => {
+                        settings_clone.fill(&config);
+                        *base_config_clone.borrow_mut() = config;
+                    }
+                    Err(e) => msg_label_clone.set_text(format!("[Error] {}", e).as_str()),
+                }
+            }
+        });
+
+        let draw_area_copy = draw_area.clone();
+        let chart_area_copy = chart_area.clone();
+        let gantt_area_copy = gantt_area.clone();
+        let manager_clone = manager.clone();
+        let history_clone = history.clone();
+        let time_adjustment_clone = time_adjustment.clone();
+        let msg_label_clone = msg_label.clone();
+        settings_button.connect_clicked(move |_| {
+            *base_config.borrow_mut() = manager_clone.borrow().config().clone();
+            settings.fill(&base_config.borrow());
+            let response = settings_dialog.run();
+            settings_dialog.hide();
+            if response != ResponseType::Ok {
+                return;
+            }
+            let config = settings.read(&base_config.borrow());
+            if let Err(e) = config.validate() {
+                msg_label_clone.set_text(format!("[Error] {}", e).as_str());
+                return;
+            }
+            // the simulation restarts with the same tasks
+            let workload = manager_clone.borrow().workload();
+            let mut manager = Manager::with_config(config);
+            manager.add_workload(&workload).unwrap();
+            *history_clone.borrow_mut() = History::new(&manager, HISTORY_INTERVAL);
+            *manager_clone.borrow_mut() = manager;
+            show_time(&time_adjustment_clone, &history_clone.borrow(), 0);
+            msg_label_clone.set_text("[Ok] settings applied.");
+            draw_area_copy.queue_draw();
+            chart_area_copy.queue_draw();
+            gantt_area_copy.queue_draw();
+        });
+
+        let draw_area_copy = draw_area.clone();
+        let chart_area_copy = chart_area.clone();
+        let gantt_area_copy = gantt_area.clone();
+        let manager_clone = manager.clone();
+        let history_clone = history.clone();
+        let time_adjustment_clone = time_adjustment.clone();
+        let msg_label_clone = msg_label.clone();
+        let window_clone = window.clone();
+        open_button.connect_clicked(move |_| {
+            // the simulation restarts with the opened workload
+            let path = match choose_file(&window_clone, "Open workload", FileChooserAction::Open, "") {
+                None => return,
+                Some(path) => path,
+            };
+            let mut manager = Manager::with_config(manager_clone.borrow().config().clone());
+            match manager.load_workload(&path) {
+                Ok(()) => {
+                    *history_clone.borrow_mut() = History::new(&manager, HISTORY_INTERVAL);
+                    *manager_clone.borrow_mut() = manager;
+                    show_time(&time_adjustment_clone, &history_clone.borrow(), 0);
+                    msg_label_clone.set_text(format!("[Ok] opened {}", path.display()).as_str());
+                    draw_area_copy.queue_draw();
+                    chart_area_copy.queue_draw();
+                    gantt_area_copy.queue_draw();
+                }
+                Err(e) => msg_label_clone.set_text(format!("[Error] {}", e).as_str()),
+            }
+        });
+
+        let manager_clone = manager.clone();
+        let msg_label_clone = msg_label.clone();
+        let window_clone = window.clone();
+        save_button.connect_clicked(move |_| {
+            let path = match choose_file(&window_clone, "Save workload", FileChooserAction::Save, "workload.toml") {
+                None => return,
+                Some(path) => path,
+            };
+            match manager_clone.borrow().save_workload(&path) {
+                Ok(()) => msg_label_clone.set_text(format!("[Ok] saved {}", path.display()).as_str()),
+                Err(e) => msg_label_clone.set_text(format!("[Error] {}", e).as_str()),
+            }
+        });
+
+        // figures are .svg, or .png by extension
+        let manager_clone = manager.clone();
+        let msg_label_clone = msg_label.clone();
+        let window_clone = window.clone();
+        export_gantt_button.connect_clicked(move |_| {
+            let path = match choose_file(&window_clone, "Export Gantt chart", FileChooserAction::Save, "gantt.svg") {
+                None => return,
+                Some(path) => path,
+            };
+            match manager_clone.borrow().export_gantt(&path) {
+                Ok(()) => msg_label_clone.set_text(format!("[Ok] exported {}", path.display()).as_str()),
+                Err(e) => msg_label_clone.set_text(format!("[Error] {}", e).as_str()),
+            }
+        });
+
+        let manager_clone = manager.clone();
+        let msg_label_clone = msg_label.clone();
+        let window_clone = window.clone();
+        export_memory_button.connect_clicked(move |_| {
+            let path = match choose_file(&window_clone, "Export memory map", FileChooserAction::Save, "memory.svg") {
+                None => return,
+                Some(path) => path,
+            };
+            match manager_clone.borrow().export_memory_map(&path) {
+                Ok(()) => msg_label_clone.set_text(format!("[Ok] exported {}", path.display()).as_str()),
+                Err(e) => msg_label_clone.set_text(format!("[Error] {}", e).as_str()),
+            }
+        });
 
         let entry_clone = entry.clone();
         let manager_clone = manager.clone();
+        let history_clone = history.clone();
+        let time_adjustment_clone = time_adjustment.clone();
+        let msg_label_clone = msg_label.clone();
         exec_button.connect_clicked(move |_| {
             // get text in entry
             let cmd = String::from(entry_clone.get_text().to_string().trim());
@@ -53,10 +311,10 @@ fn main() {
             let temp: Vec<&str> = cmd.split(char::is_whitespace).collect();
             if temp.len() != 3 && temp.len() != 4 {
                 eprintln!("[Error] invalid command");
-                msg_label.set_text("[Error] invalid command");
+                msg_label_clone.set_text("[Error] invalid command");
                 return;
             }
-            let (req_time, priority, mem_size, pre): (i32, i32, u32, Option<u32>)
+            let (req_time, priority, mem_size, pre): (i32, i32, MemSize, Option<u32>)
                 = (temp[0].parse().unwrap(),
                    temp[1].parse().unwrap(),
                    temp[2].parse().unwrap(),
@@ -67,31 +325,247 @@ fn main() {
                    }
             );
             manager_clone.borrow_mut().create_task(req_time, priority, mem_size, pre);
+            // after going back the recorded ticks past now are dropped
+            let time = manager_clone.borrow().time();
+            history_clone.borrow_mut().checkpoint(&manager_clone.borrow());
+            show_time(&time_adjustment_clone, &history_clone.borrow(), time);
             // clear text
             entry_clone.set_text("");
-            msg_label.set_text("[Ok] command executed.")
+            msg_label_clone.set_text("[Ok] command executed.")
         });
 
         let draw_area_copy = draw_area.clone();
+        let chart_area_copy = chart_area.clone();
+        let gantt_area_copy = gantt_area.clone();
         let manager_clone = manager.clone();
+        let history_clone = history.clone();
+        let time_adjustment_clone = time_adjustment.clone();
+        let last_events_clone = last_events.clone();
         next_button.connect_clicked(move |_| {
-            manager_clone.borrow_mut().advance();
+            // after going back this replays the recorded run, which is deterministic
+            let events = manager_clone.borrow_mut().step();
+            let time = manager_clone.borrow().time();
+            history_clone.borrow_mut().record(&manager_clone.borrow());
+            show_time(&time_adjustment_clone, &history_clone.borrow(), time);
+            *last_events_clone.borrow_mut() = events;
             draw_area_copy.queue_draw();
+            chart_area_copy.queue_draw();
+            gantt_area_copy.queue_draw();
+        });
+
+        // playing clicks next on a timer, starting again replaces the running timer
+        let generation = Rc::new(Cell::new(0u32));
+        let play = {
+            let generation = generation.clone();
+            let play_button = play_button.clone();
+            let next_button = next_button.clone();
+            let speed_adjustment = speed_adjustment.clone();
+            let manager_clone = manager.clone();
+            let last_events = last_events.clone();
+            let msg_label_clone = msg_label.clone();
+            Rc::new(move || {
+                generation.set(generation.get() + 1);
+                let current = generation.get();
+                let (generation, play_button, next_button) = (generation.clone(), play_button.clone(), next_button.clone());
+                let (manager_clone, last_events, msg_label_clone) = (manager_clone.clone(), last_events.clone(), msg_label_clone.clone());
+                let breakpoint_entry = breakpoint_entry.clone();
+                let interval = (1000.0 / speed_adjustment.get_value().max(1.0)) as u32;
+                glib::timeout_add_local(interval, move || {
+                    if current != generation.get() || !play_button.get_active() {
+                        return glib::Continue(false);
+                    }
+                    let stop = |msg: String| {
+                        msg_label_clone.set_text(msg.as_str());
+                        play_button.set_active(false);
+                        glib::Continue(false)
+                    };
+                    if manager_clone.borrow().is_finished() {
+                        return stop(String::from("[Ok] every task has finished."));
+                    }
+                    let breakpoints = match Breakpoint::parse_list(&breakpoint_entry.get_text()) {
+                        Ok(breakpoints) => breakpoints,
+                        Err(e) => return stop(format!("[Error] {}", e)),
+                    };
+                    next_button.clicked();
+                    let time = manager_clone.borrow().time();
+                    let hit = breakpoints.iter().find(|b| b.is_hit(time, &last_events.borrow()));
+                    match hit {
+                        Some(b) => stop(format!("[Ok] breakpoint {:?} hit at time {}", b, time)),
+                        None => glib::Continue(true),
+                    }
+                });
+            })
+        };
+
+        let play_clone = play.clone();
+        play_button.connect_toggled(move |button| {
+            button.set_label(if button.get_active() { "Pause" } else { "Play" });
+            if button.get_active() {
+                play_clone();
+            }
+        });
+
+        let play_button_clone = play_button.clone();
+        speed_adjustment.connect_value_changed(move |_| {
+            if play_button_clone.get_active() {
+                play();
+            }
+        });
+
+        let time_adjustment_clone = time_adjustment.clone();
+        prev_button.connect_clicked(move |_| {
+            time_adjustment_clone.set_value(time_adjustment_clone.get_value() - 1.0);
+        });
+
+        // moving the scrubber rebuilds the simulation at that tick
+        let draw_area_copy = draw_area.clone();
+        let chart_area_copy = chart_area.clone();
+        let gantt_area_copy = gantt_area.clone();
+        let manager_clone = manager.clone();
+        let history_clone = history.clone();
+        let msg_label_clone = msg_label.clone();
+        time_adjustment.connect_value_changed(move |adj| {
+            let tick = adj.get_value().round() as i32;
+            if tick == manager_clone.borrow().time() {
+                return;
+            }
+            let seeked = history_clone.borrow().seek(tick);
+            match seeked {
+                Ok(manager) => *manager_clone.borrow_mut() = manager,
+                Err(e) => msg_label_clone.set_text(format!("[Error] {}", e).as_str()),
+            }
+            draw_area_copy.queue_draw();
+            chart_area_copy.queue_draw();
+            gantt_area_copy.queue_draw();
+        });
+
+        // keep the newest ticks of the gantt chart in view
+        let hadjustment = gantt_scroll.get_hadjustment().unwrap();
+        hadjustment.connect_changed(|adj| {
+            adj.set_value(adj.get_upper() - adj.get_page_size());
+        });
+
+        let manager_clone = manager.clone();
+        gantt_area.connect_draw(move |area, cr| {
+            // one row per processor, one column per tick
+            let manager = manager_clone.borrow();
+            let timeline = manager.timeline();
+            let procs = manager.get_running_task().len();
+            let (gx, gy, tw, rh) = (50.0, 10.0, 12.0, 20.0);
+            let ticks = timeline.len() as i32;
+            let width = (gx + tw * ticks as f64 + 20.0) as i32;
+            let height = (gy + (rh + 4.0) * procs as f64 + 20.0) as i32;
+            if area.get_size_request() != (width.max(480), height) {
+                area.set_size_request(width.max(480), height);
+            }
+            let tx = |tick: i32| gx + tw * tick as f64;
+            let row_y = |p: usize| gy + (rh + 4.0) * p as f64;
+            cr.set_line_width(1.0);
+            cr.set_font_size(11.0);
+            for p in 0..procs {
+                cr.set_source_rgba(0.3, 0.3, 0.3, 1.0);
+                cr.move_to(5.0, row_y(p) + 14.0);
+                cr.show_text(format!("cpu {}", p).as_str());
+                // idle background
+                cr.set_source_rgba(0.9, 0.9, 0.9, 1.0);
+                cr.rectangle(gx, row_y(p), tw * ticks as f64, rh);
+                cr.fill();
+            }
+            let runs = timeline.runs();
+            for run in runs.iter() {
+                let (x, y, w) = (tx(run.start), row_y(run.processor as usize), tw * (run.end - run.start) as f64);
+                let (r, g, b, a) = pid_color(run.pid);
+                cr.set_source_rgba(r, g, b, a);
+                cr.rectangle(x, y, w, rh);
+                cr.fill();
+                cr.set_source_rgba(0.3, 0.3, 0.3, 1.0);
+                cr.rectangle(x, y, w, rh);
+                cr.stroke();
+                if w >= 20.0 {
+                    cr.move_to(x + 3.0, y + 14.0);
+                    cr.show_text(run.pid.to_string().as_str());
+                }
+            }
+            // context switches in black, preemptions in red
+            cr.set_line_width(2.0);
+            for run in runs.iter() {
+                let (x, y) = (tx(run.start), row_y(run.processor as usize));
+                if run.preempted {
+                    cr.set_source_rgba(0.9, 0.1, 0.1, 1.0);
+                } else if run.context_switch {
+                    cr.set_source_rgba(0.1, 0.1, 0.1, 1.0);
+                } else {
+                    continue;
+                }
+                cr.move_to(x, y - 2.0);
+                cr.line_to(x, y + rh + 2.0);
+                cr.stroke();
+            }
+            // time axis
+            cr.set_line_width(1.0);
+            cr.set_source_rgba(0.3, 0.3, 0.3, 1.0);
+            let ay = row_y(procs) + 2.0;
+            for tick in (0..=ticks).step_by(5) {
+                cr.move_to(tx(tick), ay - 4.0);
+                cr.line_to(tx(tick), ay);
+                cr.stroke();
+                cr.move_to(tx(tick) - 3.0, ay + 11.0);
+                cr.show_text(tick.to_string().as_str());
+            }
+            Inhibit(false)
+        });
+
+        let manager_clone = manager.clone();
+        chart_area.connect_draw(move |_, cr| {
+            // memory utilization and external fragmentation over time
+            let (cx, cy, cw, ch) = (30.0, 10.0, 300.0, 110.0);
+            cr.set_line_width(1.0);
+            cr.set_source_rgba(0.3, 0.3, 0.3, 1.0);
+            cr.rectangle(cx, cy, cw, ch);
+            cr.stroke();
+            let manager = manager_clone.borrow();
+            let history = manager.memory_history();
+            let step = cw / (history.len().max(1) as f64);
+            let plot = |value: &dyn Fn(usize) -> f64| {
+                for i in 0..history.len() {
+                    let (x, y) = (cx + step * (i as f64 + 0.5), cy + ch * (1.0 - value(i)));
+                    if i == 0 {
+                        cr.move_to(x, y);
+                    } else {
+                        cr.line_to(x, y);
+                    }
+                }
+                cr.stroke();
+            };
+            cr.set_source_rgba(0.51, 0.67, 0.87, 1.0);
+            plot(&|i| history[i].utilization);
+            cr.set_source_rgba(0.87, 0.51, 0.67, 1.0);
+            plot(&|i| history[i].external_fragmentation);
+            // legend and current values
+            let metrics = manager.memory_metrics();
+            cr.set_font_size(12.0);
+            cr.set_source_rgba(0.51, 0.67, 0.87, 1.0);
+            cr.move_to(cx + cw + 10.0, cy + 12.0);
+            cr.show_text(format!("util {:.1}%", metrics.utilization * 100.0).as_str());
+            cr.set_source_rgba(0.87, 0.51, 0.67, 1.0);
+            cr.move_to(cx + cw + 10.0, cy + 30.0);
+            cr.show_text(format!("ext frag {:.1}%", metrics.external_fragmentation * 100.0).as_str());
+            cr.set_source_rgba(0.3, 0.3, 0.3, 1.0);
+            cr.move_to(cx + cw + 10.0, cy + 48.0);
+            cr.show_text(format!("holes {}", metrics.hole_count).as_str());
+            cr.move_to(cx + cw + 10.0, cy + 66.0);
+            cr.show_text(format!("largest {}", metrics.largest_free_hole).as_str());
+            cr.move_to(cx + cw + 10.0, cy + 84.0);
+            cr.show_text(format!("failed allocs {}", metrics.allocation_failures).as_str());
+            cr.move_to(cx + cw + 10.0, cy + 102.0);
+            cr.show_text(format!("int frag {:.1} KiB", metrics.internal_fragmentation as f64 / 1024.0).as_str());
+            Inhibit(false)
         });
 
         let manager_clone = manager.clone();
         draw_area.connect_draw(move |_, cr| {
-            // make tasks colorful
-            let colors = [
-                (0.51, 0.67, 0.87, 0.8),
-                (0.51, 0.87, 0.67, 0.8),
-                (0.67, 0.51, 0.87, 0.8),
-                (0.67, 0.87, 0.51, 0.8),
-                (0.87, 0.67, 0.51, 0.8),
-                (0.87, 0.51, 0.67, 0.8),
-            ];
             let set_pid_color = |pid: &u32| {
-                let (r, g, b, a) = colors[((pid - 1) % colors.len() as u32) as usize];
+                let (r, g, b, a) = pid_color(*pid);
                 cr.set_source_rgba(r, g, b, a);
             };
             let set_text_color = || {
@@ -102,7 +576,14 @@ fn main() {
             // cr.move_to(250.0, 22.0);
             // cr.set_font_size(18.0);
             // cr.show_text(format!("Time: {}", manager_clone.borrow().time()).as_str());
-            time_label.set_text(format!("Time: {}", manager_clone.borrow().time()).as_str());
+            let (frames_used, frames_total) = manager_clone.borrow().frame_usage();
+            // effective access time with 20ns tlb and 100ns memory
+            let tlb = manager_clone.borrow().total_tlb_stats();
+            let eat = match tlb.references() {
+                0 => String::from("n/a"),
+                _ => format!("{:.1}ns", tlb.effective_access_time(20.0, 100.0)),
+            };
+            time_label.set_text(format!("Time: {}  Frames: {}/{}  EAT: {}", manager_clone.borrow().time(), frames_used, frames_total, eat).as_str());
             // draw memory
             let (bx, by, w, h) = (30.0, 30.0, 140.0, 340.0);
             set_text_color();
@@ -110,29 +591,27 @@ fn main() {
             cr.rectangle(bx, by, w, h);
             cr.stroke();
 
+            let pages = manager_clone.borrow().memory_pages() as f64;
             let cth = |mem_pos: u32| {
-                (mem_pos as f64) / (4096.0) * (h as f64)
+                (mem_pos as f64) / pages * h
             };
 
-            let (pw, ph) = (80.0, 80.0);
-            // draw processors
-            let proc1_rect = || {
-                let (px1, py1) = (260.0, 60.0);
-                cr.rectangle(px1, py1, pw, ph);
-            };
-            let proc2_rect = || {
-                let (px2, py2) = (260.0, 260.0);
-                cr.rectangle(px2, py2, pw, ph);
-            };
-            proc1_rect();
-            cr.stroke();
-            proc2_rect();
-            cr.stroke();
+            // draw processors, stacked in the height of the memory bar
+            let running = manager_clone.borrow().get_running_task();
+            let slot = h / running.len().max(1) as f64;
+            let (px, pw) = (260.0, (slot - 30.0).min(80.0));
+            let ph = pw;
+            let proc_y = |i: usize| by + slot * i as f64 + (slot - ph - 20.0) / 2.0;
+            for i in 0..running.len() {
+                cr.rectangle(px, proc_y(i), pw, ph);
+                cr.stroke();
+            }
 
             // fill mem
             // set font size
             cr.set_font_size(13.0);
-            for (pid, hole) in manager_clone.borrow().get_mem_usage().iter() {
+            for (pid, hole) in manager_clone.borrow().get_mem_usage().iter()
+                .flat_map(|(pid, holes)| holes.iter().map(move |h| (pid, h))) {
                 let (beg, _) = hole.to_tuple();
                 set_pid_color(pid);
                 cr.rectangle(bx, cth(beg) + by, w, cth(hole.get_size()));
@@ -143,35 +622,88 @@ fn main() {
                 cr.move_to(bx + w + 5.0, cth(beg) + cth(hole.get_size()) / 2.0 + by);
                 cr.show_text(format!("pid: {}", pid).as_str());
             }
-
-            // add text to proc
-            // set font size
-            cr.set_font_size(16.0);
-            let running = manager_clone.borrow().get_running_task();
-            let (r1, r2) = (running[0], running[1]);
-            if let Some(r) = r1 {
-                set_pid_color(&r);
-                proc1_rect();
-                cr.fill();
-                set_text_color();
-                cr.move_to(300.0 - 20.0, 100.0);
-                cr.show_text(format!("pid {}", r).as_str());
-            } else {
+            // numa node boundaries
+            let numa = manager_clone.borrow().numa().clone();
+            if numa.nodes().len() > 1 {
                 set_text_color();
-                cr.move_to(300.0 - 20.0, 100.0);
-                cr.show_text("idle");
+                for (i, node) in numa.nodes().iter().enumerate() {
+                    let (beg, _) = node.range();
+                    cr.move_to(bx - 25.0, by + cth(beg) + 12.0);
+                    cr.show_text(format!("N{}", i).as_str());
+                    cr.move_to(bx, by + cth(beg));
+                    cr.line_to(bx + w, by + cth(beg));
+                    cr.stroke();
+                }
             }
-            if let Some(r) = r2 {
-                set_pid_color(&r);
-                proc2_rect();
+            // shared regions are grey
+            for (shm_id, region) in manager_clone.borrow().get_shared_usage().iter() {
+                let hole = region.hole();
+                let (beg, _) = hole.to_tuple();
+                cr.set_source_rgba(0.7, 0.7, 0.7, 0.8);
+                cr.rectangle(bx, cth(beg) + by, w, cth(hole.get_size()));
                 cr.fill();
                 set_text_color();
-                cr.move_to(300.0 - 20.0, 300.0);
-                cr.show_text(format!("pid {}", r).as_str());
-            } else {
+                cr.rectangle(bx, cth(beg) + by, w, cth(hole.get_size()));
+                cr.stroke();
+                cr.move_to(bx + w + 5.0, cth(beg) + cth(hole.get_size()) / 2.0 + by);
+                cr.show_text(format!("shm: {} ({})", shm_id, region.users().len()).as_str());
+            }
+
+            // draw swap, swapped out pages are stacked by pid
+            let (sx, sw) = (400.0, 40.0);
+            set_text_color();
+            cr.rectangle(sx, by, sw, h);
+            cr.stroke();
+            cr.move_to(sx, by + h + 15.0);
+            cr.show_text("swap");
+            let swap_cap = manager_clone.borrow().swap_capacity() as f64;
+            let mut swapped: Vec<(u32, u32)> = manager_clone.borrow().get_swap_usage()
+                .iter().map(|(pid, pages)| (*pid, *pages)).collect();
+            swapped.sort();
+            let mut sy = by;
+            for (pid, pages) in swapped.iter() {
+                let sh = (*pages as f64) / swap_cap * h;
+                set_pid_color(pid);
+                cr.rectangle(sx, sy, sw, sh);
+                cr.fill();
                 set_text_color();
-                cr.move_to(300.0 - 20.0, 300.0);
-                cr.show_text("idle");
+                cr.rectangle(sx, sy, sw, sh);
+                cr.stroke();
+                sy += sh;
+            }
+
+            // add text to proc
+            // set font size
+            cr.set_font_size(16.0);
+            for (i, r) in running.iter().enumerate() {
+                if let Some(r) = r {
+                    set_pid_color(r);
+                    cr.rectangle(px, proc_y(i), pw, ph);
+                    cr.fill();
+                    set_text_color();
+                    cr.move_to(px + pw / 2.0 - 20.0, proc_y(i) + ph / 2.0);
+                    cr.show_text(format!("pid {}", r).as_str());
+                } else {
+                    set_text_color();
+                    cr.move_to(px + pw / 2.0 - 20.0, proc_y(i) + ph / 2.0);
+                    cr.show_text("idle");
+                }
+            }
+            // tlb hit rate below each processor
+            cr.set_font_size(12.0);
+            set_text_color();
+            for (i, stats) in manager_clone.borrow().tlb_stats().iter().enumerate() {
+                cr.move_to(px, proc_y(i) + ph + 15.0);
+                let hit_rate = match stats.references() {
+                    0 => String::from("n/a"),
+                    _ => format!("{:.1}%", stats.hit_rate() * 100.0),
+                };
+                cr.show_text(format!("TLB hit {}", hit_rate).as_str());
+            }
+            if numa.nodes().len() > 1 {
+                let stats = manager_clone.borrow().numa_stats();
+                cr.move_to(260.0, 390.0);
+                cr.show_text(format!("NUMA remote {}/{}", stats.remote_accesses, stats.local_accesses + stats.remote_accesses).as_str());
             }
             Inhibit(false)
         });
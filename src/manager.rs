@@ -1,37 +1,442 @@
-use crate::scheduler::Scheduler;
-use crate::task::Task;
+use crate::scheduler::{Scheduler, SchedulerSnapshot, RunRecord, RecordLengths};
+use serde::{Serialize, Deserialize};
+use crate::processor::TaskRef;
+use crate::task::{Task, Segment, Reference};
 use std::collections::HashMap;
 use crate::memory::hole::Hole;
+use crate::memory::tlb::TlbStats;
+use crate::memory::{SharedRegion, MemoryMetrics};
+use crate::memory::size::MemSize;
+use crate::memory::numa::{NumaTopology, NumaStats};
+use crate::config::SimConfig;
+use crate::workload::{Workload, TaskSpec};
+use crate::generator::GeneratorConfig;
+use crate::metrics::{TaskMetrics, AggregateMetrics};
+use crate::events::Event;
+use crate::timeline::Timeline;
+use crate::export;
+use std::sync::mpsc::Receiver;
+use std::fs;
+use std::path::Path;
 
 pub struct Manager {
     scheduler: Scheduler,
+    config: SimConfig,
     pid_counter: u32,
+    // specs of created tasks, their id is the pid
+    created: Vec<TaskSpec>,
+    // tasks that have not arrived yet, in arrival order
+    arrivals: Vec<TaskSpec>,
+}
+
+// everything needed to resume a run, event subscribers are not included
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    config: SimConfig,
+    pid_counter: u32,
+    created: Vec<TaskSpec>,
+    arrivals: Vec<TaskSpec>,
+    scheduler: SchedulerSnapshot,
+}
+
+impl Snapshot {
+    pub fn set_record(&mut self, record: RunRecord) {
+        self.scheduler.set_record(record);
+    }
+}
+
+impl Default for Manager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Manager {
     pub fn new() -> Self {
+        Self::with_config(SimConfig::default())
+    }
+    pub fn with_config(config: SimConfig) -> Self {
         Self {
-            scheduler: Scheduler::new(4, 5),
+            scheduler: Scheduler::new(&config),
+            config,
             pid_counter: 1,
+            created: vec![],
+            arrivals: vec![],
         }
     }
-    pub fn create_task(&mut self, req_time: i32, priority: i32, memory_size: u32, pre: Option<u32>) {
-        match self.scheduler.add_task(Task::new(self.pid_counter, req_time, priority, memory_size), pre) {
-            Err(e) => eprintln!("Error occurred, unable to create new task: {:?}", e),
-            _ => (),
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = self.snapshot_without_record();
+        snapshot.set_record(self.record_since(RecordLengths::default()));
+        snapshot
+    }
+    pub fn snapshot_without_record(&self) -> Snapshot {
+        // the timeline, memory history and finished tasks are left empty, see `set_record`
+        Snapshot {
+            config: self.config.clone(),
+            pid_counter: self.pid_counter,
+            created: self.created.clone(),
+            arrivals: self.arrivals.clone(),
+            scheduler: self.scheduler.snapshot_without_record(),
         }
+    }
+    pub fn record_lengths(&self) -> RecordLengths {
+        self.scheduler.record_lengths()
+    }
+    pub fn record_since(&self, lengths: RecordLengths) -> RunRecord {
+        self.scheduler.record_since(lengths)
+    }
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, String> {
+        Ok(Self {
+            scheduler: Scheduler::from_snapshot(snapshot.scheduler)?,
+            config: snapshot.config,
+            pid_counter: snapshot.pid_counter,
+            created: snapshot.created,
+            arrivals: snapshot.arrivals,
+        })
+    }
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        // snapshots are json, the maps are keyed by pid
+        let path = path.as_ref();
+        let text = serde_json::to_string(&self.snapshot()).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("unable to write {}: {}", path.display(), e))
+    }
+    pub fn load_snapshot<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        let snapshot = serde_json::from_str(&text).map_err(|e| format!("invalid snapshot {}: {}", path.display(), e))?;
+        Self::from_snapshot(snapshot)
+    }
+    pub fn config(&self) -> &SimConfig {
+        &self.config
+    }
+    pub fn create_task(&mut self, req_time: i32, priority: i32, memory_size: MemSize, pre: Option<u32>) {
+        self.create_task_with_refs(req_time, priority, memory_size, pre, vec![]);
+    }
+    pub fn create_task_with_refs(&mut self, req_time: i32, priority: i32, memory_size: MemSize, pre: Option<u32>, references: Vec<u32>) {
+        let mut spec = self.spec_now(req_time, priority, memory_size, pre);
+        spec.references = references;
+        self.submit(spec);
+    }
+    pub fn create_segmented_task(&mut self, req_time: i32, priority: i32, segments: Vec<Segment>, pre: Option<u32>, references: Vec<Reference>) {
+        let mut spec = self.spec_now(req_time, priority, MemSize::Pages(0), pre);
+        spec.segments = segments;
+        spec.segment_references = references;
+        self.submit(spec);
+    }
+    pub fn create_task_with_script(&mut self, req_time: i32, priority: i32, memory_size: MemSize, pre: Option<u32>, script: Vec<(i32, i32)>) {
+        // `script` holds (run time, size delta) pairs, like brk calls
+        let mut spec = self.spec_now(req_time, priority, memory_size, pre);
+        spec.script = script;
+        self.submit(spec);
+    }
+    pub fn create_task_with_shared(&mut self, req_time: i32, priority: i32, memory_size: MemSize, pre: Option<u32>, shared: Vec<(u32, u32)>) {
+        // `shared` holds (shared memory id, size) pairs, the same id maps the same region
+        let mut spec = self.spec_now(req_time, priority, memory_size, pre);
+        spec.shared = shared;
+        self.submit(spec);
+    }
+    pub fn create_custom_task<F>(&mut self, req_time: i32, priority: i32, memory_size: MemSize, pre: Option<u32>, setup: F)
+        where F: FnOnce(&mut Task) {
+        // changes made by `setup` are not part of the saved workload
+        let spec = self.spec_now(req_time, priority, memory_size, pre);
+        self.pid_counter += 1;
+        self.spawn(spec, setup);
+    }
+    fn spec_now(&self, req_time: i32, priority: i32, memory_size: MemSize, pre: Option<u32>) -> TaskSpec {
+        TaskSpec::new(self.pid_counter, self.time(), req_time, priority, memory_size, pre)
+    }
+    fn submit(&mut self, spec: TaskSpec) {
+        // `spec.id` must be the next pid and `spec.after` a pid
         self.pid_counter += 1;
+        if spec.arrival > self.time() {
+            let pos = self.arrivals.iter().position(|t| t.arrival > spec.arrival).unwrap_or(self.arrivals.len());
+            self.arrivals.insert(pos, spec);
+        } else {
+            self.spawn(spec, |_| ());
+        }
+    }
+    fn spawn<F>(&mut self, spec: TaskSpec, setup: F)
+        where F: FnOnce(&mut Task) {
+        // sizes are rounded up to whole pages
+        let page_size = self.scheduler.memory_manager().page_size();
+        let pages = match spec.memory.to_pages(page_size) {
+            Ok(pages) => pages,
+            Err(e) => {
+                eprintln!("Error occurred, unable to create new task: {}", e);
+                return;
+            }
+        };
+        let mut task = Task::new(spec.id, spec.burst, spec.priority, pages);
+        task.set_wasted_bytes(spec.memory.internal_fragmentation(page_size));
+        if !spec.segments.is_empty() {
+            task.set_segments(spec.segments.clone());
+            task.set_references(spec.segment_references.clone());
+        } else {
+            task.set_references(spec.references.iter().map(|p| Reference::from(*p)).collect());
+        }
+        task.set_shared(spec.shared.clone());
+        task.set_memory_script(spec.script.clone());
+        setup(&mut task);
+        match self.scheduler.add_task(task, spec.after) {
+            Err(e) => eprintln!("Error occurred, unable to create new task: {:?}", e),
+            _ => self.created.push(spec),
+        }
+    }
+    pub fn add_workload(&mut self, workload: &Workload) -> Result<(), String> {
+        // ids are renumbered to pids and arrivals count from now
+        workload.validate()?;
+        let pages = self.memory_pages();
+        if let Some(task) = workload.tasks.iter().find(|t| t.script.iter().any(|(_, delta)| *delta as i64 > pages as i64)) {
+            return Err(format!("task {} asks for more than the {} pages of memory", task.id, pages));
+        }
+        let mut id_to_pid = HashMap::new();
+        for (i, spec) in workload.sorted().iter().enumerate() {
+            id_to_pid.insert(spec.id, self.pid_counter + i as u32);
+        }
+        for mut spec in workload.sorted() {
+            spec.id = id_to_pid[&spec.id];
+            spec.after = spec.after.map(|id| id_to_pid[&id]);
+            spec.arrival += self.time();
+            self.submit(spec);
+        }
+        Ok(())
+    }
+    pub fn generate_workload(&mut self, generator: &GeneratorConfig) -> Result<(), String> {
+        // no task may need more than all of memory
+        let mut generator = generator.clone();
+        generator.max_memory = generator.max_memory.min(self.config.memory_size / 1024).max(1);
+        self.add_workload(&generator.generate()?)
+    }
+    pub fn load_workload<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let workload = Workload::load(path)?;
+        self.add_workload(&workload)
+    }
+    pub fn workload(&self) -> Workload {
+        // every task so far, including those yet to arrive
+        let mut tasks = self.created.clone();
+        tasks.extend(self.arrivals.iter().cloned());
+        Workload { tasks }
+    }
+    pub fn save_workload<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        self.workload().save(path)
     }
     pub fn advance(&mut self) {
         self.scheduler.advance_time();
+        // tasks arriving now are scheduled from the next tick on
+        while self.arrivals.first().is_some_and(|t| t.arrival <= self.time()) {
+            let spec = self.arrivals.remove(0);
+            self.spawn(spec, |_| ());
+        }
     }
-    pub fn get_mem_usage(&self) -> &HashMap<u32, Hole> {
+    pub fn subscribe<F>(&mut self, callback: F)
+        where F: FnMut(&Event) + 'static {
+        self.scheduler.events_mut().subscribe(callback);
+    }
+    pub fn step(&mut self) -> Vec<Event> {
+        // advance one tick and return its events
+        let receiver = self.events();
+        self.advance();
+        receiver.try_iter().collect()
+    }
+    pub fn events(&mut self) -> Receiver<Event> {
+        // every event from now on, read them with `try_iter`
+        self.scheduler.events_mut().receiver()
+    }
+    pub fn task_metrics(&self) -> Vec<TaskMetrics> {
+        self.scheduler.task_metrics()
+    }
+    pub fn aggregate_metrics(&self) -> AggregateMetrics {
+        self.scheduler.aggregate_metrics()
+    }
+    pub fn is_finished(&self) -> bool {
+        // every task has arrived and terminated
+        self.arrivals.is_empty() && self.scheduler.is_idle()
+    }
+    pub fn get_mem_usage(&self) -> &HashMap<u32, Vec<Hole>> {
         self.scheduler.memory_manager().get_mem_usage()
     }
-    pub fn get_running_task(&self) -> [Option<u32>; 2] {
+    pub fn queues(&self) -> Vec<(&'static str, Vec<TaskRef>)> {
+        self.scheduler.queues()
+    }
+    pub fn get_running_task(&self) -> Vec<Option<u32>> {
         self.scheduler.get_executing_tasks()
     }
+    pub fn set_numa(&mut self, node_count: u32, local_cost: u32, remote_cost: u32, aware: bool) -> Result<(), String> {
+        // split memory and the processors into `node_count` nodes
+        let config = SimConfig {
+            numa_nodes: node_count,
+            numa_local_cost: local_cost,
+            numa_remote_cost: remote_cost,
+            numa_aware: aware,
+            ..self.config.clone()
+        };
+        config.validate()?;
+        let numa = NumaTopology::split(self.memory_pages(), self.config.processors, node_count, local_cost, remote_cost);
+        self.scheduler.set_numa(numa, aware);
+        self.config = config;
+        Ok(())
+    }
+    pub fn numa(&self) -> &NumaTopology {
+        self.scheduler.numa()
+    }
+    pub fn numa_stats(&self) -> NumaStats {
+        self.scheduler.numa_stats()
+    }
+    pub fn memory_pages(&self) -> u32 {
+        let memory = self.scheduler.memory_manager();
+        (memory.size() / memory.page_size() as u64) as u32
+    }
+    pub fn memory_metrics(&self) -> MemoryMetrics {
+        self.scheduler.memory_manager().metrics()
+    }
+    pub fn timeline(&self) -> &Timeline {
+        self.scheduler.timeline()
+    }
+    pub fn export_gantt<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        export::save(&export::gantt_svg(self.timeline()), path)
+    }
+    pub fn export_memory_map<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        export::save(&export::memory_svg(self), path)
+    }
+    pub fn memory_history(&self) -> &Vec<MemoryMetrics> {
+        self.scheduler.memory_history()
+    }
+    pub fn get_shared_usage(&self) -> &HashMap<u32, SharedRegion> {
+        self.scheduler.memory_manager().get_shared_usage()
+    }
+    pub fn get_swap_usage(&self) -> &HashMap<u32, u32> {
+        self.scheduler.swap().get_swap_usage()
+    }
+    pub fn swap_capacity(&self) -> u32 {
+        self.scheduler.swap().capacity()
+    }
+    pub fn frame_usage(&self) -> (u32, u32) {
+        let frames = self.scheduler.frame_allocator();
+        (frames.demand(), frames.total_frames())
+    }
+    pub fn tlb_stats(&self) -> Vec<TlbStats> {
+        self.scheduler.tlb_stats()
+    }
+    pub fn total_tlb_stats(&self) -> TlbStats {
+        self.tlb_stats().iter().fold(TlbStats::default(), |acc, s| acc.merge(s))
+    }
     pub fn time(&self) -> i32 {
         self.scheduler.time()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(manager: &Manager, queue: &str) -> Vec<u32> {
+        manager.queues().into_iter()
+            .filter(|(name, _)| *name == queue)
+            .flat_map(|(_, tasks)| tasks.into_iter().map(|t| t.borrow().pid()))
+            .collect()
+    }
+
+    fn run_to_end(mut manager: Manager) -> Manager {
+        while !manager.is_finished() && manager.time() < 1000 {
+            manager.advance();
+        }
+        manager
+    }
+
+    #[test]
+    fn snapshot_round_trip_with_a_waiter_and_a_suspended_task() {
+        // 100 pages: pid 2 waits for pid 1, and is swapped out so that pid 3 fits
+        let config = SimConfig { memory_size: 100 * 4096, ..SimConfig::default() };
+        let mut manager = Manager::with_config(config);
+        manager.create_task(6, 1, MemSize::Pages(40), None);
+        manager.create_task(2, 1, MemSize::Pages(30), Some(1));
+        manager.create_task(3, 1, MemSize::Pages(50), None);
+        while queued(&manager, "blocked suspended").is_empty() {
+            assert!(manager.time() < 20, "no task was suspended");
+            manager.advance();
+        }
+        assert_eq!(queued(&manager, "blocked suspended"), vec![2]);
+        let text = serde_json::to_string(&manager.snapshot()).unwrap();
+        let resumed = Manager::from_snapshot(serde_json::from_str(&text).unwrap()).unwrap();
+        assert_eq!(queued(&resumed, "blocked suspended"), vec![2]);
+        let (original, resumed) = (run_to_end(manager), run_to_end(resumed));
+        assert!(original.is_finished());
+        assert_eq!(original.time(), resumed.time());
+        assert_eq!(original.timeline().runs(), resumed.timeline().runs());
+        assert_eq!(serde_json::to_value(original.task_metrics()).unwrap(),
+                   serde_json::to_value(resumed.task_metrics()).unwrap());
+        // the waiter only ran once pid 1 had finished
+        let metrics = original.task_metrics();
+        assert!(metrics[1].first_run.unwrap() >= metrics[0].completion.unwrap());
+    }
+
+    #[test]
+    fn a_segmentation_fault_aborts_the_task() {
+        // pid 1 touches page 7 of its 2 pages on its second tick
+        let mut manager = Manager::new();
+        manager.create_task_with_refs(5, 2, MemSize::Pages(2), None, vec![0, 7]);
+        manager.create_task(2, 1, MemSize::Pages(2), None);
+        let mut reasons = vec![];
+        while !manager.is_finished() {
+            assert!(manager.time() < 50, "the run did not finish");
+            reasons.extend(manager.step().into_iter().filter(|e| e.pid == Some(1)).map(|e| e.reason.name()));
+        }
+        assert!(reasons.contains(&"aborted"));
+        assert!(!reasons.contains(&"finished"));
+        let metrics = manager.task_metrics();
+        assert!(metrics[0].aborted && !metrics[1].aborted);
+        let aggregate = manager.aggregate_metrics();
+        assert_eq!((aggregate.completed, aggregate.aborted), (1, 1));
+        assert_eq!(aggregate.mean_turnaround, metrics[1].turnaround.unwrap() as f64);
+    }
+
+    #[test]
+    fn numa_aware_runs_tasks_near_their_memory() {
+        // 4 processors on 2 nodes, remote ticks cost 4 times as much
+        let config = SimConfig { processors: 4, ..SimConfig::default() };
+        let runs: Vec<(NumaStats, i32)> = [false, true].iter().map(|aware| {
+            let mut manager = Manager::with_config(config.clone());
+            manager.set_numa(2, 1, 4, *aware).unwrap();
+            for i in 0..10 {
+                manager.create_task(6, 1 + i % 3, MemSize::Pages(100 + i as u32 * 10), None);
+            }
+            let manager = run_to_end(manager);
+            assert!(manager.is_finished());
+            (manager.numa_stats(), manager.time())
+        }).collect();
+        let ((blind, blind_time), (aware, aware_time)) = (runs[0], runs[1]);
+        assert!(aware.remote_accesses < blind.remote_accesses && aware.cost < blind.cost, "{:?} {:?}", aware, blind);
+        assert!(aware_time <= blind_time);
+        // free local accesses would make remote ticks endless
+        assert!(Manager::new().set_numa(2, 0, 4, true).is_err());
+    }
+
+    #[test]
+    fn a_growth_that_can_never_fit_aborts_the_task() {
+        // 100 pages, pid 1 holds 40 and asks for 70 more
+        let config = SimConfig { memory_size: 100 * 4096, ..SimConfig::default() };
+        let mut manager = Manager::with_config(config.clone());
+        manager.create_task_with_script(10, 1, MemSize::Pages(40), None, vec![(2, 70)]);
+        let manager = run_to_end(manager);
+        assert!(manager.is_finished());
+        assert!(manager.task_metrics()[0].aborted);
+        assert_eq!(manager.memory_metrics().allocation_failures, 0);
+        // more than all of memory is refused up front
+        let mut workload = Workload::default();
+        workload.tasks.push(TaskSpec { script: vec![(1, 101)], ..TaskSpec::new(1, 0, 5, 1, MemSize::Pages(1), None) });
+        assert!(Manager::with_config(config).add_workload(&workload).is_err());
+    }
+
+    #[test]
+    fn a_waiting_growth_counts_one_allocation_failure() {
+        // pid 2 waits for pid 1 to free its 60 pages
+        let config = SimConfig { memory_size: 100 * 4096, ..SimConfig::default() };
+        let mut manager = Manager::with_config(config);
+        manager.create_task(8, 1, MemSize::Pages(60), None);
+        manager.create_task_with_script(4, 1, MemSize::Pages(20), None, vec![(1, 50)]);
+        let manager = run_to_end(manager);
+        assert!(manager.is_finished());
+        let metrics = manager.task_metrics();
+        assert!(!metrics[1].aborted && metrics[1].blocked > 0);
+        assert_eq!(manager.memory_metrics().allocation_failures, 1);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FramePolicy {
+    // resident set = pages referenced in the last `window` references of the task
+    WorkingSet { window: u32 },
+    // grow on a fault within `threshold` references of the previous fault,
+    // otherwise drop every page not used since the previous fault
+    PageFaultFrequency { threshold: u32 },
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ResidentSet {
+    // page -> virtual time of last reference
+    pages: HashMap<u32, u32>,
+    clock: u32,
+    last_fault: u32,
+    faults: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FrameAllocator {
+    total_frames: u32,
+    policy: FramePolicy,
+    sets: HashMap<u32, ResidentSet>,
+    // size of the resident set a task had when it was released
+    released: HashMap<u32, u32>,
+}
+
+impl FrameAllocator {
+    pub fn new(total_frames: u32, policy: FramePolicy) -> Self {
+        FrameAllocator {
+            total_frames,
+            policy,
+            sets: HashMap::new(),
+            released: HashMap::new(),
+        }
+    }
+    pub fn reference(&mut self, pid: u32, page: u32) -> bool {
+        // return true on page fault
+        self.released.remove(&pid);
+        let set = self.sets.entry(pid).or_default();
+        set.clock += 1;
+        let now = set.clock;
+        let fault = match self.policy {
+            FramePolicy::WorkingSet { window } => {
+                // look up before pruning, the page referenced `window` references ago is still resident
+                let fault = !set.pages.contains_key(&page);
+                set.pages.retain(|_, last| now - *last < window);
+                fault
+            }
+            FramePolicy::PageFaultFrequency { threshold } => {
+                let fault = !set.pages.contains_key(&page);
+                if fault {
+                    if now - set.last_fault > threshold {
+                        let last_fault = set.last_fault;
+                        set.pages.retain(|_, last| *last >= last_fault);
+                    }
+                    set.last_fault = now;
+                }
+                fault
+            }
+        };
+        if fault {
+            set.faults += 1;
+        }
+        set.pages.insert(page, now);
+        fault
+    }
+    pub fn release(&mut self, pid: u32) {
+        if let Some(set) = self.sets.remove(&pid) {
+            self.released.insert(pid, set.pages.len() as u32);
+        }
+    }
+    pub fn forget(&mut self, pid: u32) {
+        self.sets.remove(&pid);
+        self.released.remove(&pid);
+    }
+    pub fn resident_size(&self, pid: u32) -> u32 {
+        self.sets.get(&pid).map_or(0, |s| s.pages.len() as u32)
+    }
+    pub fn faults(&self, pid: u32) -> u32 {
+        self.sets.get(&pid).map_or(0, |s| s.faults)
+    }
+    pub fn demand(&self) -> u32 {
+        self.sets.values().map(|s| s.pages.len() as u32).sum()
+    }
+    pub fn total_frames(&self) -> u32 {
+        self.total_frames
+    }
+    pub fn is_thrashing(&self) -> bool {
+        self.demand() > self.total_frames
+    }
+    pub fn can_admit(&self, pid: u32) -> bool {
+        // a released task comes back only if its old resident set fits
+        let need = self.released.get(&pid).copied().unwrap_or(0);
+        self.demand() + need <= self.total_frames
+    }
+    pub fn policy(&self) -> FramePolicy {
+        self.policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn faults(policy: FramePolicy, pages: &[u32], rounds: usize) -> u32 {
+        let mut frames = FrameAllocator::new(16, policy);
+        for _ in 0..rounds {
+            for page in pages {
+                frames.reference(1, *page);
+            }
+        }
+        frames.faults(1)
+    }
+
+    #[test]
+    fn working_set_holds_a_loop_that_fits_the_window() {
+        // only the first pass faults
+        assert_eq!(faults(FramePolicy::WorkingSet { window: 2 }, &[1, 2], 5), 2);
+        assert_eq!(faults(FramePolicy::WorkingSet { window: 4 }, &[1, 2, 3, 4], 5), 4);
+    }
+
+    #[test]
+    fn working_set_drops_pages_outside_the_window() {
+        // every reference is to the page that just left the window
+        assert_eq!(faults(FramePolicy::WorkingSet { window: 2 }, &[1, 2, 3], 4), 12);
+        let mut frames = FrameAllocator::new(16, FramePolicy::WorkingSet { window: 3 });
+        for page in [1, 2, 3, 4] {
+            frames.reference(1, page);
+        }
+        assert_eq!(frames.resident_size(1), 3);
+    }
+}
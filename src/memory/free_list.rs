@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::memory::hole::*;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Placement {
+    FirstFit,
+    BestFit,
+}
+
+// largest hole size over address ranges, leaf `i` holds the size of the hole starting at `i`
+#[derive(Default, Clone)]
+struct MaxTree {
+    leaves: usize,
+    nodes: Vec<u32>,
+}
+
+impl MaxTree {
+    fn set(&mut self, addr: u32, size: u32) {
+        if addr as usize >= self.leaves {
+            self.grow(addr as usize + 1);
+        }
+        let mut i = addr as usize + self.leaves;
+        self.nodes[i] = size;
+        while i > 1 {
+            i /= 2;
+            self.nodes[i] = self.nodes[2 * i].max(self.nodes[2 * i + 1]);
+        }
+    }
+    fn get(&self, addr: u32) -> u32 {
+        if (addr as usize) < self.leaves { self.nodes[addr as usize + self.leaves] } else { 0 }
+    }
+    fn grow(&mut self, min_leaves: usize) {
+        // double up to a power of two, a memory of n pages ends up with 2n nodes
+        let leaves = min_leaves.next_power_of_two();
+        let mut nodes = vec![0; 2 * leaves];
+        nodes[leaves..leaves + self.leaves].copy_from_slice(&self.nodes[self.leaves..]);
+        for i in (1..leaves).rev() {
+            nodes[i] = nodes[2 * i].max(nodes[2 * i + 1]);
+        }
+        self.leaves = leaves;
+        self.nodes = nodes;
+    }
+    fn max(&self) -> u32 {
+        self.nodes.get(1).copied().unwrap_or(0)
+    }
+    fn first_fit(&self, size: u32) -> Option<u32> {
+        // the lowest address with a hole of at least `size`, one root to leaf walk
+        if self.max() < size {
+            return None;
+        }
+        let mut i = 1;
+        while i < self.leaves {
+            i = if self.nodes[2 * i] >= size { 2 * i } else { 2 * i + 1 };
+        }
+        Some((i - self.leaves) as u32)
+    }
+}
+
+// free holes indexed by address for coalescing, by size for best fit and in a `MaxTree` for first fit,
+// snapshots only store the holes
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(from = "BTreeMap<u32, u32>", into = "BTreeMap<u32, u32>")]
+pub struct FreeList {
+    by_addr: BTreeMap<u32, u32>,
+    by_size: BTreeSet<(u32, u32)>,
+    by_first_fit: MaxTree,
+    free_size: u32,
+}
+
+impl From<BTreeMap<u32, u32>> for FreeList {
+    fn from(holes: BTreeMap<u32, u32>) -> Self {
+        let mut list = FreeList::new();
+        for (beg, end) in holes {
+            list.add(beg, end);
+        }
+        list
+    }
+}
+
+impl From<FreeList> for BTreeMap<u32, u32> {
+    fn from(list: FreeList) -> Self {
+        list.by_addr
+    }
+}
+
+impl FreeList {
+    pub fn new() -> Self {
+        FreeList {
+            by_addr: BTreeMap::new(),
+            by_size: BTreeSet::new(),
+            by_first_fit: MaxTree::default(),
+            free_size: 0,
+        }
+    }
+    fn add(&mut self, beg: u32, end: u32) {
+        self.by_addr.insert(beg, end);
+        self.by_size.insert((end - beg, beg));
+        self.by_first_fit.set(beg, end - beg);
+        self.free_size += end - beg;
+    }
+    fn remove(&mut self, beg: u32, end: u32) {
+        self.by_addr.remove(&beg);
+        self.by_size.remove(&(end - beg, beg));
+        self.by_first_fit.set(beg, 0);
+        self.free_size -= end - beg;
+    }
+    pub fn take(&mut self, req_size: u32, placement: Placement) -> Option<Hole> {
+        // cut `req_size` from the head of a fitting hole
+        let (beg, end) = match placement {
+            Placement::BestFit => {
+                let (size, beg) = *self.by_size.range((req_size, 0)..).next()?;
+                (beg, beg + size)
+            }
+            Placement::FirstFit => {
+                let beg = self.by_first_fit.first_fit(req_size)?;
+                (beg, self.by_addr[&beg])
+            }
+        };
+        self.remove(beg, end);
+        if end - beg > req_size {
+            self.add(beg + req_size, end);
+        }
+        Some(Hole::new(beg, beg + req_size).unwrap())
+    }
+    pub fn take_in(&mut self, req_size: u32, placement: Placement, lo: u32, hi: u32) -> Option<Hole> {
+        // like `take`, but the result must lie in [lo, hi)
+        let first = self.by_addr.range(..=lo).next_back().map_or(lo, |(beg, _)| *beg);
+        let mut fit: Option<(u32, u32, u32, u32)> = None;
+        for (&beg, &end) in self.by_addr.range(first..hi) {
+            let (s, e) = (beg.max(lo), end.min(hi));
+            if e <= s || e - s < req_size {
+                continue;
+            }
+            match (placement, fit) {
+                (Placement::FirstFit, _) => {
+                    fit = Some((s, e - s, beg, end));
+                    break;
+                }
+                (Placement::BestFit, Some((_, size, _, _))) if size <= e - s => (),
+                (Placement::BestFit, _) => fit = Some((s, e - s, beg, end)),
+            }
+        }
+        let (s, _, beg, end) = fit?;
+        self.remove(beg, end);
+        if s > beg {
+            self.add(beg, s);
+        }
+        if end > s + req_size {
+            self.add(s + req_size, end);
+        }
+        Some(Hole::new(s, s + req_size).unwrap())
+    }
+    pub fn free_in(&self, lo: u32, hi: u32) -> u32 {
+        // free units inside [lo, hi)
+        let first = self.by_addr.range(..=lo).next_back().map_or(lo, |(beg, _)| *beg);
+        self.by_addr.range(first..hi)
+            .map(|(beg, end)| (*end).min(hi).saturating_sub((*beg).max(lo)))
+            .sum()
+    }
+    pub fn insert(&mut self, hole: Hole) {
+        // coalesce with the neighbours on both sides
+        let (mut beg, mut end) = hole.to_tuple();
+        if let Some((&prev_beg, &prev_end)) = self.by_addr.range(..beg).next_back() {
+            if prev_end == beg {
+                self.remove(prev_beg, prev_end);
+                beg = prev_beg;
+            }
+        }
+        if let Some(&next_end) = self.by_addr.get(&end) {
+            self.remove(end, next_end);
+            end = next_end;
+        }
+        self.add(beg, end);
+    }
+    pub fn largest(&self) -> u32 {
+        self.by_size.iter().next_back().map_or(0, |(size, _)| *size)
+    }
+    pub fn free_size(&self) -> u32 {
+        self.free_size
+    }
+    pub fn len(&self) -> usize {
+        self.by_addr.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.by_addr.is_empty()
+    }
+    pub fn check(&self) -> Result<(), String> {
+        // holes must be sorted, non-empty, non-overlapping and non-adjacent
+        let mut prev_end: Option<u32> = None;
+        let mut free_size = 0;
+        for (beg, end) in self.by_addr.iter() {
+            if beg >= end {
+                return Err(format!("empty hole at {}", beg));
+            }
+            if let Some(prev_end) = prev_end {
+                if prev_end >= *beg {
+                    return Err(format!("hole at {} overlaps or touches the previous one", beg));
+                }
+            }
+            if !self.by_size.contains(&(end - beg, *beg)) {
+                return Err(format!("hole at {} missing from the size index", beg));
+            }
+            if self.by_first_fit.get(*beg) != end - beg {
+                return Err(format!("hole at {} missing from the first fit tree", beg));
+            }
+            free_size += end - beg;
+            prev_end = Some(*end);
+        }
+        if self.by_size.len() != self.by_addr.len() {
+            return Err(String::from("size index out of sync"));
+        }
+        if self.by_first_fit.max() != self.largest() {
+            return Err(String::from("first fit tree out of sync"));
+        }
+        if free_size != self.free_size {
+            return Err(format!("free size is {}, holes add up to {}", self.free_size, free_size));
+        }
+        Ok(())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = Hole> + '_ {
+        // holes in address order
+        self.by_addr.iter().map(|(beg, end)| Hole::new(*beg, *end).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLACEMENTS: [Placement; 2] = [Placement::FirstFit, Placement::BestFit];
+
+    fn list(holes: &[(u32, u32)]) -> FreeList {
+        let mut list = FreeList::new();
+        for (beg, end) in holes {
+            list.insert(Hole::new(*beg, *end).unwrap());
+        }
+        list
+    }
+
+    fn holes(list: &FreeList) -> Vec<(u32, u32)> {
+        list.iter().map(|h| h.to_tuple()).collect()
+    }
+
+    #[test]
+    fn take_splits_the_head_off() {
+        for placement in PLACEMENTS {
+            let mut list = list(&[(0, 100)]);
+            assert_eq!(list.take(30, placement).unwrap().to_tuple(), (0, 30));
+            assert_eq!(holes(&list), vec![(30, 100)]);
+            assert_eq!(list.free_size(), 70);
+            list.check().unwrap();
+        }
+    }
+
+    #[test]
+    fn take_uses_up_an_exact_fit() {
+        for placement in PLACEMENTS {
+            let mut list = list(&[(0, 10), (20, 50)]);
+            assert_eq!(list.take(10, placement).unwrap().to_tuple(), (0, 10));
+            assert_eq!(holes(&list), vec![(20, 50)]);
+            assert!(list.take(31, placement).is_none());
+            list.check().unwrap();
+        }
+    }
+
+    #[test]
+    fn first_fit_and_best_fit_pick_different_holes() {
+        let mut first = list(&[(0, 50), (60, 70)]);
+        assert_eq!(first.take(10, Placement::FirstFit).unwrap().to_tuple(), (0, 10));
+        assert_eq!(holes(&first), vec![(10, 50), (60, 70)]);
+        let mut best = list(&[(0, 50), (60, 70)]);
+        assert_eq!(best.take(10, Placement::BestFit).unwrap().to_tuple(), (60, 70));
+        assert_eq!(holes(&best), vec![(0, 50)]);
+    }
+
+    #[test]
+    fn first_fit_takes_the_lowest_hole_that_fits() {
+        let mut list = list(&[(0, 5), (10, 30), (40, 45), (50, 100)]);
+        assert_eq!(list.take(20, Placement::FirstFit).unwrap().to_tuple(), (10, 30));
+        assert_eq!(list.take(5, Placement::FirstFit).unwrap().to_tuple(), (0, 5));
+        assert_eq!(list.take(30, Placement::FirstFit).unwrap().to_tuple(), (50, 80));
+        assert_eq!(list.take(5, Placement::FirstFit).unwrap().to_tuple(), (40, 45));
+        assert!(list.take(21, Placement::FirstFit).is_none());
+        // a hole past every address seen so far grows the tree
+        list.insert(Hole::new(5000, 5100).unwrap());
+        assert_eq!(list.take(50, Placement::FirstFit).unwrap().to_tuple(), (5000, 5050));
+        list.check().unwrap();
+    }
+
+    #[test]
+    fn snapshots_rebuild_the_indexes() {
+        let list = list(&[(0, 5), (10, 30)]);
+        let text = serde_json::to_string(&list).unwrap();
+        assert_eq!(text, r#"{"0":5,"10":30}"#);
+        let mut list: FreeList = serde_json::from_str(&text).unwrap();
+        list.check().unwrap();
+        assert_eq!(list.take(10, Placement::FirstFit).unwrap().to_tuple(), (10, 20));
+    }
+
+    #[test]
+    fn insert_coalesces_with_both_neighbours() {
+        for placement in PLACEMENTS {
+            let mut list = list(&[(0, 100)]);
+            let a = list.take(10, placement).unwrap();
+            let b = list.take(10, placement).unwrap();
+            let c = list.take(10, placement).unwrap();
+            list.insert(a);
+            list.insert(c);
+            assert_eq!(holes(&list), vec![(0, 10), (20, 100)]);
+            list.insert(b);
+            assert_eq!(holes(&list), vec![(0, 100)]);
+            assert_eq!(list.largest(), 100);
+            list.check().unwrap();
+        }
+    }
+
+    fn check_err(list: &FreeList) -> String {
+        list.check().unwrap_err()
+    }
+
+    #[test]
+    fn check_finds_an_empty_hole() {
+        let mut list = list(&[(0, 10)]);
+        list.by_addr.insert(20, 20);
+        list.by_size.insert((0, 20));
+        assert_eq!(check_err(&list), "empty hole at 20");
+    }
+
+    #[test]
+    fn check_finds_overlapping_and_touching_holes() {
+        let mut overlapping = list(&[(0, 10)]);
+        overlapping.add(5, 20);
+        assert_eq!(check_err(&overlapping), "hole at 5 overlaps or touches the previous one");
+        let mut touching = list(&[(0, 10)]);
+        touching.add(10, 20);
+        assert_eq!(check_err(&touching), "hole at 10 overlaps or touches the previous one");
+    }
+
+    #[test]
+    fn check_finds_a_stale_size_index() {
+        let mut missing = list(&[(0, 10)]);
+        missing.by_addr.insert(20, 30);
+        missing.free_size += 10;
+        assert_eq!(check_err(&missing), "hole at 20 missing from the size index");
+        let mut extra = list(&[(0, 10)]);
+        extra.by_size.insert((5, 20));
+        assert_eq!(check_err(&extra), "size index out of sync");
+    }
+
+    #[test]
+    fn check_finds_a_stale_first_fit_tree() {
+        let mut missing = list(&[(0, 10)]);
+        missing.by_first_fit.set(0, 4);
+        assert_eq!(check_err(&missing), "hole at 0 missing from the first fit tree");
+        let mut extra = list(&[(0, 10)]);
+        extra.by_first_fit.set(20, 30);
+        assert_eq!(check_err(&extra), "first fit tree out of sync");
+    }
+
+    #[test]
+    fn check_finds_a_wrong_free_size() {
+        let mut list = list(&[(0, 10)]);
+        list.free_size += 1;
+        assert_eq!(check_err(&list), "free size is 11, holes add up to 10");
+    }
+
+    #[test]
+    fn take_in_splits_around_the_range() {
+        for placement in PLACEMENTS {
+            let mut list = list(&[(0, 100)]);
+            assert_eq!(list.take_in(10, placement, 40, 60).unwrap().to_tuple(), (40, 50));
+            assert_eq!(holes(&list), vec![(0, 40), (50, 100)]);
+            assert!(list.take_in(30, placement, 40, 60).is_none());
+            list.check().unwrap();
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
+use serde::{Serialize, Deserialize};
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Hole {
     beg: u32,
     end: u32,
@@ -56,6 +57,15 @@ impl Hole {
             hole
         }
     }
+    pub fn split_tail(&mut self, size: u32) -> Result<Self, &'static str> {
+        if size >= self.get_size() {
+            Err("hole is not large enough")
+        } else {
+            let hole = Self::new(self.end - size, self.end);
+            self.end -= size;
+            hole
+        }
+    }
     pub fn to_tuple(&self) -> (u32, u32) {
         (self.beg, self.end)
     }
@@ -1,19 +1,64 @@
 pub mod hole;
+pub mod frame;
+pub mod tlb;
+pub mod swap;
+pub mod free_list;
+pub mod size;
+pub mod numa;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::memory::hole::*;
+use crate::memory::free_list::*;
+use serde::{Serialize, Deserialize};
 
 #[derive(Debug)]
 pub enum MemoryError {
     OutOfMemory,
     PIDInvalid,
+    SwapFull,
+    InvalidSize,
+    // a shared region is mapped with another size than it was created with
+    SizeMismatch,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MemoryMetrics {
+    // 1 - largest free hole / total free memory
+    pub external_fragmentation: f64,
+    pub largest_free_hole: u32,
+    pub hole_count: u32,
+    pub utilization: f64,
+    pub allocation_failures: u32,
+    // bytes lost to rounding requests up to pages
+    pub internal_fragmentation: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedRegion {
+    hole: Hole,
+    users: HashSet<u32>,
+}
+
+impl SharedRegion {
+    pub fn hole(&self) -> Hole {
+        self.hole
+    }
+    pub fn users(&self) -> &HashSet<u32> {
+        &self.users
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MemoryManager {
     size: u64,
     page_size: u32,
-    pid_to_mem: HashMap<u32, Hole>,
-    holes: Vec<Hole>,
+    pid_to_mem: HashMap<u32, Vec<Hole>>,
+    // shared memory id -> region mapped by several pids
+    shared: HashMap<u32, SharedRegion>,
+    holes: FreeList,
+    placement: Placement,
+    pid_to_waste: HashMap<u32, u64>,
+    alloc_failures: u32,
 }
 
 impl MemoryManager {
@@ -22,62 +67,217 @@ impl MemoryManager {
             size,
             page_size,
             pid_to_mem: HashMap::new(),
-            holes: vec![Hole::new(0, (size / page_size as u64) as u32).unwrap()],
+            shared: HashMap::new(),
+            holes: {
+                let mut holes = FreeList::new();
+                // memory smaller than a page has no frames at all
+                if let Ok(hole) = Hole::new(0, (size / page_size as u64) as u32) {
+                    holes.insert(hole);
+                }
+                holes
+            },
+            placement: Placement::FirstFit,
+            pid_to_waste: HashMap::new(),
+            alloc_failures: 0,
         }
     }
+    pub fn set_placement(&mut self, placement: Placement) {
+        self.placement = placement;
+    }
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
     pub fn allocate(&mut self, req_size: u32, pid: u32) -> Result<Hole, MemoryError> {
-        // first fit
-        let mut fit: Option<usize> = None;
-        let mut entire_hole = false;
-        for i in 0..self.holes.len() {
-            if self.holes[i].get_size() >= req_size {
-                fit = Some(i);
-                entire_hole = self.holes[i].get_size() == req_size;
-                break;
+        let hole = self.take_hole(req_size)?;
+        self.pid_to_mem.entry(pid).or_default().push(hole);
+        self.debug_check();
+        Ok(hole)
+    }
+    fn take_hole(&mut self, req_size: u32) -> Result<Hole, MemoryError> {
+        if req_size == 0 {
+            return Err(MemoryError::InvalidSize);
+        }
+        match self.holes.take(req_size, self.placement) {
+            None => {
+                self.alloc_failures += 1;
+                Err(MemoryError::OutOfMemory)
             }
+            Some(hole) => Ok(hole),
         }
-        match fit {
-            None => Err(MemoryError::OutOfMemory),
-            Some(i) => {
-                let hole = if entire_hole {
-                    let temp = self.holes[i];
-                    self.holes.remove(i);
-                    temp
-                } else {
-                    self.holes[i].split_head(req_size).unwrap()
-                };
-                self.pid_to_mem.insert(pid, hole);
-                Ok(hole)
+    }
+    pub fn allocate_segments(&mut self, sizes: &[u32], pid: u32) -> Result<Vec<Hole>, MemoryError> {
+        self.allocate_segments_near(sizes, pid, None)
+    }
+    pub fn allocate_segments_near(&mut self, sizes: &[u32], pid: u32, prefer: Option<(u32, u32)>) -> Result<Vec<Hole>, MemoryError> {
+        // place every segment on its own, all or nothing
+        // no segments take no memory, the pid is still known to `free`
+        if sizes.contains(&0) {
+            return Err(MemoryError::InvalidSize);
+        }
+        let known = self.pid_to_mem.contains_key(&pid);
+        self.pid_to_mem.entry(pid).or_default();
+        let mut holes = vec![];
+        for size in sizes.iter() {
+            // try the preferred range first, then anywhere
+            let near = prefer.and_then(|(lo, hi)| self.holes.take_in(*size, self.placement, lo, hi));
+            if let Some(hole) = near {
+                self.pid_to_mem.get_mut(&pid).unwrap().push(hole);
+                holes.push(hole);
+                continue;
+            }
+            match self.allocate(*size, pid) {
+                Ok(hole) => holes.push(hole),
+                Err(e) => {
+                    for hole in holes {
+                        self.release(pid, hole);
+                    }
+                    if !known {
+                        self.pid_to_mem.remove(&pid);
+                    }
+                    self.debug_check();
+                    return Err(e);
+                }
             }
         }
+        self.debug_check();
+        Ok(holes)
+    }
+    pub fn largest_hole(&self) -> u32 {
+        self.holes.largest()
+    }
+    pub fn largest_hole_for(&self, pid: u32) -> u32 {
+        // the largest hole there would be once every other pid had freed its memory
+        let mut kept: Vec<(u32, u32)> = self.pid_to_mem.get(&pid).into_iter().flatten().map(|h| h.to_tuple()).collect();
+        kept.extend(self.shared.values().filter(|r| r.users.contains(&pid)).map(|r| r.hole.to_tuple()));
+        kept.sort_unstable();
+        let total = (self.size / self.page_size as u64) as u32;
+        let (mut largest, mut end) = (0, 0);
+        for (beg, next_end) in kept {
+            largest = largest.max(beg.saturating_sub(end));
+            end = end.max(next_end);
+        }
+        largest.max(total.saturating_sub(end))
+    }
+    pub fn free_in(&self, range: (u32, u32)) -> u32 {
+        self.holes.free_in(range.0, range.1)
     }
     pub fn free(&mut self, pid: u32) -> Result<(), MemoryError> {
-        if !self.pid_to_mem.contains_key(&pid) {
-            Err(MemoryError::PIDInvalid)
-        } else {
-            let hole = self.pid_to_mem[&pid];
-            self.pid_to_mem.remove(&pid);
-            let mut pos: usize = 0;
-            for i in 0..self.holes.len() {
-                if self.holes[i] > hole {
-                    pos = i;
-                    break;
+        self.pid_to_waste.remove(&pid);
+        match self.pid_to_mem.remove(&pid) {
+            None => Err(MemoryError::PIDInvalid),
+            Some(holes) => {
+                for hole in holes {
+                    self.free_hole(hole);
                 }
+                self.debug_check();
+                Ok(())
             }
-            if self.holes[pos].test_adjacency(&hole) {
-                self.holes[pos].merge_into_self(&hole)
-                    .expect("hole merge failed");
+        }
+    }
+    pub fn shrink(&mut self, size: u32, pid: u32) -> Result<(), MemoryError> {
+        // give back `size` units from the most recently allocated regions of `pid`
+        let mut size = size;
+        while size > 0 {
+            let last = match self.pid_to_mem.get_mut(&pid).and_then(|holes| holes.last_mut()) {
+                None => return Err(MemoryError::PIDInvalid),
+                Some(last) => last,
+            };
+            if last.get_size() > size {
+                let tail = last.split_tail(size).unwrap();
+                self.free_hole(tail);
+                size = 0;
             } else {
-                self.holes.insert(pos, hole);
+                let last = *last;
+                size -= last.get_size();
+                self.release(pid, last);
             }
-            if pos > 0 {
-                if self.holes[pos - 1].test_adjacency(&self.holes[pos]) {
-                    let temp = self.holes[pos];
-                    self.holes[pos - 1].merge_into_self(&temp).unwrap();
-                    self.holes.remove(pos);
-                }
+        }
+        self.debug_check();
+        Ok(())
+    }
+    pub fn record_waste(&mut self, pid: u32, bytes: u64) {
+        // internal fragmentation of the regions of `pid`
+        if bytes > 0 {
+            self.pid_to_waste.insert(pid, bytes);
+        }
+    }
+    pub fn attach_shared(&mut self, shm_id: u32, size: u32, pid: u32) -> Result<Hole, MemoryError> {
+        // the first user creates the region, later users map the same one
+        if let Some(region) = self.shared.get_mut(&shm_id) {
+            if region.hole.get_size() != size {
+                return Err(MemoryError::SizeMismatch);
+            }
+            region.users.insert(pid);
+            return Ok(region.hole);
+        }
+        let hole = self.take_hole(size)?;
+        let mut users = HashSet::new();
+        users.insert(pid);
+        self.shared.insert(shm_id, SharedRegion { hole, users });
+        self.debug_check();
+        Ok(hole)
+    }
+    pub fn detach_shared(&mut self, pid: u32) {
+        // a region is freed once its last user is gone
+        let mut unused = vec![];
+        for (id, region) in self.shared.iter_mut() {
+            if region.users.remove(&pid) && region.users.is_empty() {
+                unused.push(*id);
+            }
+        }
+        for id in unused {
+            let region = self.shared.remove(&id).unwrap();
+            self.free_hole(region.hole);
+        }
+        self.debug_check();
+    }
+    pub fn sole_shared_size(&self, pid: u32) -> u32 {
+        // shared memory that would be freed if `pid` detached
+        self.shared.values()
+            .filter(|r| r.users.len() == 1 && r.users.contains(&pid))
+            .map(|r| r.hole.get_size())
+            .sum()
+    }
+    fn release(&mut self, pid: u32, hole: Hole) {
+        // give back a single region of `pid`
+        if let Some(holes) = self.pid_to_mem.get_mut(&pid) {
+            holes.retain(|h| *h != hole);
+        }
+        self.free_hole(hole);
+    }
+    fn free_hole(&mut self, hole: Hole) {
+        self.holes.insert(hole);
+    }
+    pub fn check_invariants(&self) -> Result<(), String> {
+        self.holes.check()?;
+        // holes, private and shared regions must tile memory exactly
+        let mut ranges: Vec<(u32, u32)> = self.holes.iter().map(|h| h.to_tuple()).collect();
+        for holes in self.pid_to_mem.values() {
+            ranges.extend(holes.iter().map(|h| h.to_tuple()));
+        }
+        for region in self.shared.values() {
+            ranges.push(region.hole.to_tuple());
+        }
+        ranges.sort();
+        let mut next = 0;
+        for (beg, end) in ranges {
+            if beg != next {
+                return Err(format!("range ({}, {}) found where {} was expected", beg, end, next));
+            }
+            next = end;
+        }
+        let total = (self.size / self.page_size as u64) as u32;
+        if next != total {
+            return Err(format!("ranges end at {}, memory ends at {}", next, total));
+        }
+        Ok(())
+    }
+    fn debug_check(&self) {
+        // rescans all of memory, so only with the check-invariants feature
+        if cfg!(any(test, feature = "check-invariants")) {
+            if let Err(e) = self.check_invariants() {
+                panic!("memory manager invariant violated: {}", e);
             }
-            Ok(())
         }
     }
     pub fn size(&self) -> u64 {
@@ -88,11 +288,122 @@ impl MemoryManager {
     }
     pub fn print(&self) {
         println!("mem: ");
-        for h in &self.holes {
+        for h in self.holes.iter() {
             println!("{:?}", h);
         }
     }
-    pub fn get_mem_usage(&self) -> &HashMap<u32, Hole> {
+    pub fn metrics(&self) -> MemoryMetrics {
+        let total = (self.size / self.page_size as u64) as u32;
+        let free = self.holes.free_size();
+        let largest = self.holes.largest();
+        MemoryMetrics {
+            external_fragmentation: if free == 0 { 0.0 } else { 1.0 - largest as f64 / free as f64 },
+            largest_free_hole: largest,
+            hole_count: self.holes.len() as u32,
+            utilization: (total - free) as f64 / total as f64,
+            allocation_failures: self.alloc_failures,
+            internal_fragmentation: self.pid_to_waste.values().sum(),
+        }
+    }
+    pub fn get_mem_usage(&self) -> &HashMap<u32, Vec<Hole>> {
         &self.pid_to_mem
     }
-}
\ No newline at end of file
+    pub fn get_shared_usage(&self) -> &HashMap<u32, SharedRegion> {
+        &self.shared
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> MemoryManager {
+        // 100 pages
+        let mut manager = MemoryManager::new(409600, 4096);
+        manager.allocate(10, 1).unwrap();
+        manager.allocate(20, 2).unwrap();
+        manager.attach_shared(7, 5, 1).unwrap();
+        manager
+    }
+
+    #[test]
+    fn a_consistent_manager_passes() {
+        let mut manager = manager();
+        manager.check_invariants().unwrap();
+        manager.free(1).unwrap();
+        manager.detach_shared(1);
+        manager.check_invariants().unwrap();
+    }
+
+    fn holes(manager: &MemoryManager) -> Vec<(u32, u32)> {
+        manager.holes.iter().map(|h| h.to_tuple()).collect()
+    }
+
+    fn full() -> MemoryManager {
+        // 100 pages and no holes
+        let mut manager = MemoryManager::new(409600, 4096);
+        for (pid, size) in [(1, 30), (2, 50), (3, 20)] {
+            manager.allocate(size, pid).unwrap();
+        }
+        assert!(holes(&manager).is_empty());
+        manager.check_invariants().unwrap();
+        manager
+    }
+
+    #[test]
+    fn free_on_a_full_memory() {
+        for order in [[1, 2, 3], [3, 2, 1]] {
+            let mut manager = full();
+            for pid in order {
+                manager.free(pid).unwrap();
+                manager.check_invariants().unwrap();
+            }
+            assert_eq!(holes(&manager), vec![(0, 100)]);
+        }
+    }
+
+    #[test]
+    fn a_freed_hole_goes_after_every_other_hole() {
+        let mut manager = full();
+        manager.free(1).unwrap();
+        manager.free(3).unwrap();
+        manager.check_invariants().unwrap();
+        assert_eq!(holes(&manager), vec![(0, 30), (80, 100)]);
+        manager.free(2).unwrap();
+        manager.check_invariants().unwrap();
+        assert_eq!(holes(&manager), vec![(0, 100)]);
+    }
+
+    #[test]
+    fn a_leaked_region_is_a_gap() {
+        let mut manager = manager();
+        manager.pid_to_mem.remove(&2);
+        let e = manager.check_invariants().unwrap_err();
+        assert!(e.contains("was expected"), "{}", e);
+    }
+
+    #[test]
+    fn a_region_owned_twice_overlaps() {
+        let mut manager = manager();
+        let hole = manager.pid_to_mem[&1][0];
+        manager.pid_to_mem.insert(3, vec![hole]);
+        let e = manager.check_invariants().unwrap_err();
+        assert!(e.contains("was expected"), "{}", e);
+    }
+
+    #[test]
+    fn ranges_must_cover_all_of_memory() {
+        let mut manager = manager();
+        manager.size += 4096;
+        let e = manager.check_invariants().unwrap_err();
+        assert!(e.contains("memory ends at 101"), "{}", e);
+    }
+
+    #[test]
+    fn a_double_free_overlaps() {
+        let mut manager = manager();
+        let hole = manager.pid_to_mem[&2][0];
+        manager.holes.insert(hole);
+        let e = manager.check_invariants().unwrap_err();
+        assert!(e.contains("was expected"), "{}", e);
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumaNode {
+    // page range [beg, end) of the node
+    beg: u32,
+    end: u32,
+    processors: Vec<u32>,
+}
+
+impl NumaNode {
+    pub fn range(&self) -> (u32, u32) {
+        (self.beg, self.end)
+    }
+    pub fn processors(&self) -> &Vec<u32> {
+        &self.processors
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumaTopology {
+    nodes: Vec<NumaNode>,
+    local_cost: u32,
+    remote_cost: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct NumaStats {
+    pub local_accesses: u64,
+    pub remote_accesses: u64,
+    pub cost: u64,
+}
+
+impl NumaTopology {
+    pub fn uniform(pages: u32, processor_count: u32) -> Self {
+        Self::split(pages, processor_count, 1, 1, 1)
+    }
+    pub fn split(pages: u32, processor_count: u32, node_count: u32, local_cost: u32, remote_cost: u32) -> Self {
+        // memory and processors are divided evenly, the last node takes the rest
+        let node_count = node_count.max(1);
+        let mut nodes = vec![];
+        for i in 0..node_count {
+            let beg = pages / node_count * i;
+            let end = if i + 1 == node_count { pages } else { pages / node_count * (i + 1) };
+            let processors = (0..processor_count).filter(|p| p * node_count / processor_count.max(1) == i).collect();
+            nodes.push(NumaNode { beg, end, processors });
+        }
+        NumaTopology {
+            nodes,
+            local_cost,
+            remote_cost,
+        }
+    }
+    pub fn nodes(&self) -> &Vec<NumaNode> {
+        &self.nodes
+    }
+    pub fn node_of_page(&self, page: u32) -> Option<usize> {
+        self.nodes.iter().position(|n| n.beg <= page && page < n.end)
+    }
+    pub fn node_of_processor(&self, proc_id: u32) -> Option<usize> {
+        self.nodes.iter().position(|n| n.processors.contains(&proc_id))
+    }
+    pub fn is_local(&self, proc_id: u32, node: usize) -> bool {
+        self.node_of_processor(proc_id) == Some(node)
+    }
+    pub fn local_cost(&self) -> u32 {
+        self.local_cost
+    }
+    pub fn access_cost(&self, proc_id: u32, node: usize) -> u32 {
+        if self.is_local(proc_id, node) {
+            self.local_cost
+        } else {
+            self.remote_cost
+        }
+    }
+}
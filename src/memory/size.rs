@@ -0,0 +1,148 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MemSize {
+    Pages(u32),
+    Bytes(u64),
+}
+
+impl MemSize {
+    // sizes past u64 saturate, `to_pages` then rejects them
+    pub fn kib(n: u64) -> Self {
+        MemSize::Bytes(n.saturating_mul(1024))
+    }
+    pub fn mib(n: u64) -> Self {
+        MemSize::Bytes(n.saturating_mul(1024 * 1024))
+    }
+    pub fn bytes(&self, page_size: u32) -> u64 {
+        match self {
+            MemSize::Pages(n) => *n as u64 * page_size as u64,
+            MemSize::Bytes(n) => *n,
+        }
+    }
+    pub fn to_pages(&self, page_size: u32) -> Result<u32, String> {
+        // round up to whole pages
+        match self {
+            MemSize::Pages(n) => Ok(*n),
+            MemSize::Bytes(n) => u32::try_from(n.div_ceil(page_size as u64))
+                .map_err(|_| format!("memory size too large: {}", self)),
+        }
+    }
+    pub fn internal_fragmentation(&self, page_size: u32) -> u64 {
+        // bytes wasted in the last page
+        let page_size = page_size as u64;
+        match self {
+            MemSize::Pages(_) => 0,
+            MemSize::Bytes(n) => (page_size - n % page_size) % page_size,
+        }
+    }
+}
+
+impl FromStr for MemSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // "400" and "400p" are pages, otherwise B, K/KiB or M/MiB
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (num, unit) = s.split_at(split);
+        let n: u64 = num.parse().map_err(|_| format!("invalid memory size: {}", s))?;
+        let bytes = |unit: u64| n.checked_mul(unit).map(MemSize::Bytes).ok_or(format!("memory size too large: {}", s));
+        match unit.trim() {
+            "" | "p" | "pages" => u32::try_from(n).map(MemSize::Pages).map_err(|_| format!("too many pages: {}", s)),
+            "B" => Ok(MemSize::Bytes(n)),
+            "K" | "KiB" | "KB" => bytes(1024),
+            "M" | "MiB" | "MB" => bytes(1024 * 1024),
+            _ => Err(format!("unknown memory unit: {}", unit)),
+        }
+    }
+}
+
+impl fmt::Display for MemSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the largest unit that keeps the size exact
+        match self {
+            MemSize::Pages(n) => write!(f, "{}", n),
+            MemSize::Bytes(n) if *n > 0 && n % (1024 * 1024) == 0 => write!(f, "{}MiB", n / 1024 / 1024),
+            MemSize::Bytes(n) if *n > 0 && n % 1024 == 0 => write!(f, "{}KiB", n / 1024),
+            MemSize::Bytes(n) => write!(f, "{}B", n),
+        }
+    }
+}
+
+// pages are written as numbers, bytes as the strings `from_str` accepts, e.g. "1600KiB"
+impl Serialize for MemSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MemSize::Pages(n) => serializer.serialize_u32(*n),
+            MemSize::Bytes(_) => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawMemSize {
+    Pages(u32),
+    Text(String),
+}
+
+impl<'de> Deserialize<'de> for MemSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // a bare number is a page count
+        match RawMemSize::deserialize(deserializer)? {
+            RawMemSize::Pages(n) => Ok(MemSize::Pages(n)),
+            RawMemSize::Text(s) => s.parse().map_err(D::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<MemSize, String> {
+        s.parse()
+    }
+
+    #[test]
+    fn parses_every_unit() {
+        assert_eq!(parse("400"), Ok(MemSize::Pages(400)));
+        assert_eq!(parse("400p"), Ok(MemSize::Pages(400)));
+        assert_eq!(parse("400 pages"), Ok(MemSize::Pages(400)));
+        assert_eq!(parse("100B"), Ok(MemSize::Bytes(100)));
+        for s in ["3K", "3KiB", "3KB"] {
+            assert_eq!(parse(s), Ok(MemSize::Bytes(3 * 1024)));
+        }
+        for s in ["2M", "2MiB", "2MB"] {
+            assert_eq!(parse(s), Ok(MemSize::Bytes(2 * 1024 * 1024)));
+        }
+        assert!(parse("4G").is_err());
+        assert!(parse("KiB").is_err());
+    }
+
+    #[test]
+    fn bytes_round_up_to_whole_pages() {
+        let size = parse("4097B").unwrap();
+        assert_eq!(size.to_pages(4096), Ok(2));
+        assert_eq!(size.internal_fragmentation(4096), 4095);
+        assert_eq!(parse("8KiB").unwrap().to_pages(4096), Ok(2));
+        assert_eq!(parse("8KiB").unwrap().internal_fragmentation(4096), 0);
+        assert_eq!(parse("0B").unwrap().to_pages(4096), Ok(0));
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert_eq!(parse("18014398509481984K"), Err(String::from("memory size too large: 18014398509481984K")));
+        assert!(parse("17592186044416M").is_err());
+        assert!(parse("4294967296").is_err());
+        assert!(parse("99999999999999999999B").is_err());
+        // parses, but is more than u32 pages
+        assert!(parse("18014398509481983K").unwrap().to_pages(4096).is_err());
+        assert!(MemSize::kib(u64::MAX).to_pages(4096).is_err());
+    }
+}
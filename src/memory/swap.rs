@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::memory::MemoryError;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SwapDevice {
+    capacity: u32,
+    // ticks needed to move one page
+    page_time: f64,
+    pid_to_swap: HashMap<u32, u32>,
+    // the device serves one transfer at a time
+    busy_until: i32,
+}
+
+impl SwapDevice {
+    pub fn new(capacity: u32, page_time: f64) -> Self {
+        SwapDevice {
+            capacity,
+            page_time,
+            pid_to_swap: HashMap::new(),
+            busy_until: 0,
+        }
+    }
+    fn transfer(&mut self, pages: u32, now: i32) -> i32 {
+        // return the time the transfer completes
+        let cost = (pages as f64 * self.page_time).ceil() as i32;
+        self.busy_until = self.busy_until.max(now) + cost;
+        self.busy_until
+    }
+    pub fn swap_out(&mut self, pid: u32, pages: u32, now: i32) -> Result<i32, MemoryError> {
+        if self.pid_to_swap.contains_key(&pid) {
+            return Err(MemoryError::PIDInvalid);
+        }
+        if self.used() + pages > self.capacity {
+            return Err(MemoryError::SwapFull);
+        }
+        self.pid_to_swap.insert(pid, pages);
+        Ok(self.transfer(pages, now))
+    }
+    pub fn swap_in(&mut self, pid: u32, now: i32) -> Result<i32, MemoryError> {
+        match self.pid_to_swap.remove(&pid) {
+            None => Err(MemoryError::PIDInvalid),
+            Some(pages) => Ok(self.transfer(pages, now)),
+        }
+    }
+    pub fn used(&self) -> u32 {
+        self.pid_to_swap.values().sum()
+    }
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+    pub fn busy_until(&self) -> i32 {
+        self.busy_until
+    }
+    pub fn get_swap_usage(&self) -> &HashMap<u32, u32> {
+        &self.pid_to_swap
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlbPolicy {
+    Fifo,
+    Lru,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct TlbEntry {
+    asid: u32,
+    page: u32,
+    // insertion time for FIFO, last use for LRU
+    stamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TlbStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub flushes: u64,
+}
+
+impl TlbStats {
+    pub fn references(&self) -> u64 {
+        self.hits + self.misses
+    }
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.references();
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+    pub fn effective_access_time(&self, tlb_time: f64, mem_time: f64) -> f64 {
+        // a miss walks a single level page table before the access
+        let hit_rate = self.hit_rate();
+        hit_rate * (tlb_time + mem_time) + (1.0 - hit_rate) * (tlb_time + 2.0 * mem_time)
+    }
+    pub fn merge(&self, other: &Self) -> Self {
+        TlbStats {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            flushes: self.flushes + other.flushes,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tlb {
+    sets: Vec<Vec<TlbEntry>>,
+    ways: usize,
+    policy: TlbPolicy,
+    use_asid: bool,
+    clock: u64,
+    stats: TlbStats,
+}
+
+impl Tlb {
+    pub fn new(size: usize, ways: usize, policy: TlbPolicy, use_asid: bool) -> Self {
+        let ways = ways.max(1).min(size.max(1));
+        let set_count = (size / ways).max(1);
+        Tlb {
+            sets: vec![Vec::with_capacity(ways); set_count],
+            ways,
+            policy,
+            use_asid,
+            clock: 0,
+            stats: TlbStats::default(),
+        }
+    }
+    pub fn lookup(&mut self, asid: u32, page: u32) -> bool {
+        // return true on hit, a miss loads the translation
+        self.clock += 1;
+        let set_count = self.sets.len();
+        let set = &mut self.sets[page as usize % set_count];
+        if let Some(entry) = set.iter_mut().find(|e| e.asid == asid && e.page == page) {
+            if let TlbPolicy::Lru = self.policy {
+                entry.stamp = self.clock;
+            }
+            self.stats.hits += 1;
+            return true;
+        }
+        self.stats.misses += 1;
+        if set.len() >= self.ways {
+            let victim = (0..set.len()).min_by_key(|&i| set[i].stamp).unwrap();
+            set.remove(victim);
+        }
+        set.push(TlbEntry {
+            asid,
+            page,
+            stamp: self.clock,
+        });
+        false
+    }
+    pub fn flush(&mut self) {
+        for set in self.sets.iter_mut() {
+            set.clear();
+        }
+        self.stats.flushes += 1;
+    }
+    pub fn context_switch(&mut self) {
+        // tagged entries survive a switch
+        if !self.use_asid {
+            self.flush();
+        }
+    }
+    pub fn stats(&self) -> TlbStats {
+        self.stats
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Serialize, Deserialize};
+use crate::task::TaskTimes;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TaskMetrics {
+    pub pid: u32,
+    pub arrival: i32,
+    pub burst: i32,
+    pub first_run: Option<i32>,
+    pub completion: Option<i32>,
+    pub waiting: i32,
+    pub blocked: i32,
+    // terminated by a fault, not counted as completed
+    pub aborted: bool,
+    // the rest is only known once the task has run or finished
+    pub turnaround: Option<i32>,
+    pub response: Option<i32>,
+    // turnaround / burst, 1 means the task never waited
+    pub normalized_turnaround: Option<f64>,
+}
+
+impl TaskMetrics {
+    pub fn new(pid: u32, times: &TaskTimes, aborted: bool) -> Self {
+        let turnaround = times.completion.map(|c| c - times.arrival);
+        TaskMetrics {
+            pid,
+            arrival: times.arrival,
+            burst: times.burst,
+            first_run: times.first_run,
+            completion: times.completion,
+            waiting: times.waiting,
+            blocked: times.blocked,
+            aborted,
+            turnaround,
+            response: times.first_run.map(|f| f - times.arrival),
+            normalized_turnaround: turnaround.map(|t| t as f64 / times.burst.max(1) as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct AggregateMetrics {
+    pub completed: u32,
+    pub aborted: u32,
+    // means over completed tasks
+    pub mean_turnaround: f64,
+    pub mean_waiting: f64,
+    pub mean_response: f64,
+    pub mean_normalized_turnaround: f64,
+    // completed tasks per tick
+    pub throughput: f64,
+    // busy processor ticks / all processor ticks
+    pub cpu_utilization: f64,
+    // Jain's index of burst / turnaround, 1 when every task is slowed down equally
+    pub fairness: f64,
+}
+
+pub fn jain_index(xs: &[f64]) -> f64 {
+    // (sum x)^2 / (n * sum x^2)
+    let sum: f64 = xs.iter().sum();
+    let sum_sq: f64 = xs.iter().map(|x| x * x).sum();
+    if sum_sq == 0.0 {
+        return 1.0;
+    }
+    sum * sum / (xs.len() as f64 * sum_sq)
+}
+
+impl AggregateMetrics {
+    pub fn new(tasks: &[TaskMetrics], time: i32, busy_ticks: u64, processor_count: usize) -> Self {
+        let done: Vec<&TaskMetrics> = tasks.iter().filter(|t| t.completion.is_some() && !t.aborted).collect();
+        let n = done.len() as f64;
+        let mean = |f: &dyn Fn(&TaskMetrics) -> f64| {
+            if done.is_empty() { 0.0 } else { done.iter().map(|t| f(t)).sum::<f64>() / n }
+        };
+        let speeds: Vec<f64> = done.iter().map(|t| 1.0 / t.normalized_turnaround.unwrap().max(f64::MIN_POSITIVE)).collect();
+        let ticks = time.max(0) as f64;
+        AggregateMetrics {
+            completed: done.len() as u32,
+            aborted: tasks.iter().filter(|t| t.aborted).count() as u32,
+            mean_turnaround: mean(&|t| t.turnaround.unwrap() as f64),
+            mean_waiting: mean(&|t| t.waiting as f64),
+            mean_response: mean(&|t| t.response.unwrap_or(0) as f64),
+            mean_normalized_turnaround: mean(&|t| t.normalized_turnaround.unwrap()),
+            throughput: if ticks == 0.0 { 0.0 } else { n / ticks },
+            cpu_utilization: if ticks == 0.0 || processor_count == 0 {
+                0.0
+            } else {
+                busy_ticks as f64 / (ticks * processor_count as f64)
+            },
+            fairness: jain_index(&speeds),
+        }
+    }
+}
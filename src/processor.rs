@@ -1,28 +1,50 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 use crate::task::*;
+use crate::memory::tlb::*;
+use crate::events::*;
+use serde::{Serialize, Deserialize};
 
 pub type TaskRef = Rc<RefCell<Task>>;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Processor {
     proc_id: u32,
+    // snapshots store the pid instead
+    #[serde(skip)]
     executing_task: Option<TaskRef>,
+    tlb: Tlb,
+    // pid whose translations are in the tlb
+    last_pid: Option<u32>,
 }
 
 impl Processor {
-    pub fn new(proc_id: u32) -> Self {
+    pub fn new(proc_id: u32, tlb: Tlb) -> Self {
         Self {
             proc_id,
             executing_task: None,
+            tlb,
+            last_pid: None,
         }
     }
-    pub fn run_task(&mut self) {
+    pub fn run_task(&mut self, time: i32, events: &mut EventLog) -> Option<(u32, Access)> {
+        // return (pid, access) if the task referenced memory
         if let Some(task) = &mut self.executing_task {
             let mut task = task.borrow_mut();
-            println!("Proc {}: Running {}, req_time {}, sch_time {}", self.proc_id, task.pid(), task.request_time(), task.sch_time());
             task.decrement_time(1);
+            let pid = task.pid();
+            let state = Some(TaskState::of(&task));
+            events.emit(Event::new(time, Reason::Ran { remaining: task.request_time() }).task(pid, state, state).on(self.proc_id));
+            let access = task.next_reference()?;
+            if let Access::Page(page) = access {
+                if !self.tlb.lookup(pid, page) {
+                    events.emit(Event::new(time, Reason::TlbMiss { page }).task(pid, state, state).on(self.proc_id));
+                }
+            }
+            Some((pid, access))
         } else {
-            println!("Proc {}: idle", self.proc_id);
+            events.emit(Event::new(time, Reason::Idle).on(self.proc_id));
+            None
         }
     }
     pub fn is_task_finished(&self) -> bool {
@@ -37,6 +59,11 @@ impl Processor {
         // do nothing but return `None` if `task` is None and `self.executing_task` is not `None`
         let mut cur_task = self.executing_task.clone();
         if let Some(task) = task {
+            let pid = task.borrow().pid();
+            if self.last_pid != Some(pid) {
+                self.tlb.context_switch();
+                self.last_pid = Some(pid);
+            }
             self.executing_task = Some(task);
         } else {
             if let Some(task) = &mut self.executing_task {
@@ -50,10 +77,20 @@ impl Processor {
         }
         cur_task
     }
+    pub fn release_task(&mut self) -> Option<TaskRef> {
+        // stop the executing task without touching its state
+        self.executing_task.take()
+    }
     pub fn proc_id(&self) -> u32 {
         self.proc_id
     }
+    pub fn tlb(&self) -> &Tlb {
+        &self.tlb
+    }
     pub fn executing_task(&self) -> Option<TaskRef> {
         self.executing_task.clone()
     }
+    pub fn set_executing_task(&mut self, task: Option<TaskRef>) {
+        self.executing_task = task;
+    }
 }
\ No newline at end of file
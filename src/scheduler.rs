@@ -1,12 +1,23 @@
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{BinaryHeap, HashMap, HashSet, BTreeMap, BTreeSet};
+use serde::{Serialize, Deserialize};
 use crate::task::*;
 use crate::task::ProcessState::*;
 use crate::memory::*;
+use crate::memory::hole::Hole;
+use crate::memory::frame::*;
+use crate::memory::tlb::TlbStats;
+use crate::memory::swap::SwapDevice;
+use crate::memory::numa::*;
 use std::rc::Rc;
 use std::cell::RefCell;
 use crate::processor::*;
-use crate::scheduler::SchedulerError::{InvalidCondition, InvalidPid};
+use crate::scheduler::SchedulerError::{InvalidCondition, InvalidPid, InvalidSegment, InvalidScript};
 use crate::condition::{Condition, ConditionRef};
+use crate::config::SimConfig;
+use crate::metrics::{TaskMetrics, AggregateMetrics};
+use crate::events::*;
+use crate::timeline::Timeline;
+use crate::memory::tlb::Tlb;
 use min_max_heap::MinMaxHeap;
 
 #[derive(Debug)]
@@ -14,6 +25,9 @@ pub enum SchedulerError {
     UnknownError,
     InvalidCondition,
     InvalidPid,
+    // an empty segment or shared region
+    InvalidSegment,
+    InvalidScript,
 }
 
 pub struct Scheduler {
@@ -25,14 +39,123 @@ pub struct Scheduler {
     time: i32,
     time_slice: u32,
     slots: u32,
-    processors: [Processor; 2],
+    processors: Vec<Processor>,
     memory_manager: MemoryManager,
+    frame_allocator: FrameAllocator,
+    swap: SwapDevice,
+    memory_history: Vec<MemoryMetrics>,
+    numa: NumaTopology,
+    // place memory on the least used node and run tasks near their memory
+    numa_aware: bool,
+    numa_stats: NumaStats,
     pid_to_trigger: HashMap<u32, ConditionRef>,
     valid_pid: HashSet<u32>,
+    // metrics of terminated tasks
+    finished: Vec<TaskMetrics>,
+    // ticks processors spent running a task
+    busy_ticks: u64,
+    events: EventLog,
+    timeline: Timeline,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TaskSnapshot {
+    task: Task,
+    // pid whose termination the task waits for
+    waits_for: Option<u32>,
+}
+
+// the scheduler with every task stored once and referenced by pid
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SchedulerSnapshot {
+    time: i32,
+    time_slice: u32,
+    slots: u32,
+    tasks: Vec<TaskSnapshot>,
+    new_queue: Vec<u32>,
+    task_queue: Vec<u32>,
+    blocked_queue: Vec<u32>,
+    blocked_suspend_queue: Vec<u32>,
+    ready_suspend_queue: Vec<u32>,
+    processors: Vec<Processor>,
+    // pid running on every processor
+    running: Vec<Option<u32>>,
+    memory_manager: MemoryManager,
+    frame_allocator: FrameAllocator,
+    swap: SwapDevice,
+    memory_history: Vec<MemoryMetrics>,
+    numa: NumaTopology,
+    numa_aware: bool,
+    numa_stats: NumaStats,
+    // whether the condition of every pid is set, i.e. the task terminated
+    triggers: BTreeMap<u32, bool>,
+    valid_pid: BTreeSet<u32>,
+    finished: Vec<TaskMetrics>,
+    busy_ticks: u64,
+    timeline: Timeline,
+}
+
+impl SchedulerSnapshot {
+    pub fn set_record(&mut self, record: RunRecord) {
+        self.memory_history = record.memory_history;
+        self.finished = record.finished;
+        self.timeline = record.timeline;
+    }
+}
+
+// what only grows during a run, `History` keeps it once instead of in every snapshot
+#[derive(Clone, Default)]
+pub struct RunRecord {
+    memory_history: Vec<MemoryMetrics>,
+    finished: Vec<TaskMetrics>,
+    timeline: Timeline,
+}
+
+// how much of a `RunRecord` there was at some time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordLengths {
+    memory_history: usize,
+    finished: usize,
+    ticks: usize,
+}
+
+impl RunRecord {
+    pub fn lengths(&self) -> RecordLengths {
+        RecordLengths {
+            memory_history: self.memory_history.len(),
+            finished: self.finished.len(),
+            ticks: self.timeline.len(),
+        }
+    }
+    pub fn append(&mut self, tail: RunRecord) {
+        // `tail` continues this record, see `Scheduler::record_since`
+        self.memory_history.extend(tail.memory_history);
+        self.finished.extend(tail.finished);
+        self.timeline.append(tail.timeline);
+    }
+    pub fn truncate(&mut self, lengths: RecordLengths) {
+        self.memory_history.truncate(lengths.memory_history);
+        self.finished.truncate(lengths.finished);
+        self.timeline.truncate(lengths.ticks);
+    }
+    pub fn prefix(&self, lengths: RecordLengths) -> RunRecord {
+        RunRecord {
+            memory_history: self.memory_history[..lengths.memory_history].to_vec(),
+            finished: self.finished[..lengths.finished].to_vec(),
+            timeline: self.timeline.prefix(lengths.ticks),
+        }
+    }
 }
 
 impl Scheduler {
-    pub fn new(time_slice: u32, slots: u32) -> Self {
+    pub fn new(config: &SimConfig) -> Self {
+        let processors = (0..config.processors)
+            .map(|i| Processor::new(i, Tlb::new(config.tlb_size, config.tlb_ways, config.tlb_policy, config.tlb_asid)))
+            .collect();
+        let mut memory_manager = MemoryManager::new(config.memory_size, config.page_size);
+        memory_manager.set_placement(config.placement);
+        let numa = NumaTopology::split(config.memory_pages(), config.processors, config.numa_nodes,
+                                       config.numa_local_cost, config.numa_remote_cost);
         Scheduler {
             new_queue: BinaryHeap::new(),
             task_queue: MinMaxHeap::new(),
@@ -40,19 +163,170 @@ impl Scheduler {
             ready_suspend_queue: BinaryHeap::new(),
             blocked_suspend_queue: BinaryHeap::new(),
             time: 0,
-            time_slice,
-            slots,
-            processors: [Processor::new(0), Processor::new(1)],
-            memory_manager: MemoryManager::new(16777216, 4096),
+            time_slice: config.time_slice,
+            slots: config.slots,
+            processors,
+            memory_manager,
+            frame_allocator: FrameAllocator::new(config.frames, config.frame_policy),
+            swap: SwapDevice::new(config.swap_capacity, config.swap_page_time),
+            memory_history: vec![],
+            numa,
+            numa_aware: config.numa_aware,
+            numa_stats: NumaStats::default(),
             pid_to_trigger: HashMap::new(),
             valid_pid: HashSet::new(),
+            finished: vec![],
+            busy_ticks: 0,
+            events: EventLog::new(),
+            timeline: Timeline::new(),
         }
     }
+    pub fn snapshot(&self) -> SchedulerSnapshot {
+        let mut snapshot = self.snapshot_without_record();
+        snapshot.set_record(self.record_since(RecordLengths::default()));
+        snapshot
+    }
+    pub fn record_lengths(&self) -> RecordLengths {
+        RecordLengths {
+            memory_history: self.memory_history.len(),
+            finished: self.finished.len(),
+            ticks: self.timeline.len(),
+        }
+    }
+    pub fn record_since(&self, lengths: RecordLengths) -> RunRecord {
+        // the part of the record added after it had `lengths`
+        RunRecord {
+            memory_history: self.memory_history[lengths.memory_history..].to_vec(),
+            finished: self.finished[lengths.finished..].to_vec(),
+            timeline: self.timeline.tail(lengths.ticks),
+        }
+    }
+    pub fn snapshot_without_record(&self) -> SchedulerSnapshot {
+        // tasks and conditions are shared through `Rc`s, store them by pid
+        let pids = |tasks: Vec<&TaskRef>| tasks.iter().map(|t| t.borrow().pid()).collect();
+        let waits_for = |task: &Task| {
+            let cond = task.cond()?;
+            self.pid_to_trigger.iter().find(|(_, tri)| Rc::ptr_eq(tri, &cond)).map(|(pid, _)| *pid)
+        };
+        let mut tasks: Vec<TaskSnapshot> = self.live_tasks().iter()
+            .map(|t| {
+                let task = t.borrow();
+                TaskSnapshot {
+                    task: task.clone(),
+                    waits_for: waits_for(&task),
+                }
+            })
+            .collect();
+        tasks.sort_by_key(|t| t.task.pid());
+        tasks.dedup_by_key(|t| t.task.pid());
+        SchedulerSnapshot {
+            time: self.time,
+            time_slice: self.time_slice,
+            slots: self.slots,
+            tasks,
+            new_queue: pids(self.new_queue.iter().collect()),
+            task_queue: pids(self.task_queue.iter().collect()),
+            blocked_queue: pids(self.blocked_queue.iter().collect()),
+            blocked_suspend_queue: pids(self.blocked_suspend_queue.iter().collect()),
+            ready_suspend_queue: pids(self.ready_suspend_queue.iter().collect()),
+            processors: self.processors.clone(),
+            running: self.get_executing_tasks(),
+            memory_manager: self.memory_manager.clone(),
+            frame_allocator: self.frame_allocator.clone(),
+            swap: self.swap.clone(),
+            memory_history: vec![],
+            numa: self.numa.clone(),
+            numa_aware: self.numa_aware,
+            numa_stats: self.numa_stats,
+            triggers: self.pid_to_trigger.iter().map(|(pid, tri)| (*pid, tri.borrow().is_done())).collect(),
+            valid_pid: self.valid_pid.iter().copied().collect(),
+            finished: vec![],
+            busy_ticks: self.busy_ticks,
+            timeline: Timeline::new(),
+        }
+    }
+    pub fn from_snapshot(snapshot: SchedulerSnapshot) -> Result<Self, String> {
+        // rebuild the shared conditions first, then the tasks pointing to them
+        let pid_to_trigger: HashMap<u32, ConditionRef> = snapshot.triggers.iter()
+            .map(|(pid, done)| {
+                let mut cond = Condition::new();
+                if *done {
+                    cond.set_ok();
+                }
+                (*pid, Rc::new(RefCell::new(cond)))
+            })
+            .collect();
+        let mut tasks: HashMap<u32, TaskRef> = HashMap::new();
+        for TaskSnapshot { mut task, waits_for } in snapshot.tasks {
+            if let Some(pid) = waits_for {
+                let tri = pid_to_trigger.get(&pid).ok_or(format!("unknown condition of pid {}", pid))?;
+                task.set_cond(Some(tri.clone()));
+            }
+            tasks.insert(task.pid(), Rc::new(RefCell::new(task)));
+        }
+        let task = |pid: &u32| tasks.get(pid).cloned().ok_or(format!("unknown pid {} in snapshot", pid));
+        let queue = |pids: &Vec<u32>| pids.iter().map(task).collect::<Result<Vec<TaskRef>, String>>();
+        let mut processors = snapshot.processors;
+        for (proc, pid) in processors.iter_mut().zip(snapshot.running.iter()) {
+            proc.set_executing_task(pid.as_ref().map(task).transpose()?);
+        }
+        Ok(Scheduler {
+            new_queue: queue(&snapshot.new_queue)?.into_iter().collect(),
+            task_queue: queue(&snapshot.task_queue)?.into_iter().collect(),
+            blocked_queue: queue(&snapshot.blocked_queue)?.into_iter().collect(),
+            blocked_suspend_queue: queue(&snapshot.blocked_suspend_queue)?.into_iter().collect(),
+            ready_suspend_queue: queue(&snapshot.ready_suspend_queue)?.into_iter().collect(),
+            time: snapshot.time,
+            time_slice: snapshot.time_slice,
+            slots: snapshot.slots,
+            processors,
+            memory_manager: snapshot.memory_manager,
+            frame_allocator: snapshot.frame_allocator,
+            swap: snapshot.swap,
+            memory_history: snapshot.memory_history,
+            numa: snapshot.numa,
+            numa_aware: snapshot.numa_aware,
+            numa_stats: snapshot.numa_stats,
+            pid_to_trigger,
+            valid_pid: snapshot.valid_pid.into_iter().collect(),
+            finished: snapshot.finished,
+            busy_ticks: snapshot.busy_ticks,
+            events: EventLog::new(),
+            timeline: snapshot.timeline,
+        })
+    }
+    pub fn events_mut(&mut self) -> &mut EventLog {
+        &mut self.events
+    }
+    fn emit_task(&mut self, task: &TaskRef, from: Option<TaskState>, reason: Reason) {
+        self.emit_task_on(task, from, reason, None);
+    }
+    fn emit_task_on(&mut self, task: &TaskRef, from: Option<TaskState>, reason: Reason, proc: Option<u32>) {
+        // `to` is the state the task is in now, `proc` the processor it came off
+        let (pid, to) = (task.borrow().pid(), TaskState::of(&task.borrow()));
+        let event = Event::new(self.time, reason).task(pid, from, Some(to));
+        self.events.emit(match proc {
+            Some(proc) => event.on(proc),
+            None => event,
+        });
+    }
     pub fn add_task(&mut self, mut task: Task, cond: Option<u32>) -> Result<(), SchedulerError> {
         // validate pid
         if self.valid_pid.contains(&task.pid()) {
             return Err(InvalidPid);
         }
+        if task.segments().iter().any(|s| s.size == 0) || task.shared().iter().any(|(_, size)| *size == 0) {
+            return Err(InvalidSegment);
+        }
+        // requests are checked after a tick of running, one at run time 0 would never happen
+        if task.memory_script().iter().any(|(at, _)| *at <= 0) {
+            return Err(InvalidScript);
+        }
+        // a request for more than all of memory could never be met
+        let pages = (self.memory_manager.size() / self.memory_manager.page_size() as u64) as i64;
+        if task.memory_script().iter().any(|(_, delta)| *delta as i64 > pages) {
+            return Err(InvalidScript);
+        }
         // check condition
         if let Some(pid) = cond {
             if !self.valid_pid.contains(&pid) {
@@ -69,8 +343,10 @@ impl Scheduler {
         self.valid_pid.insert(task.pid());
 
         task.set_state(New);
-        println!("New task: {:?}", task);
-        self.new_queue.push(Rc::new(RefCell::new(task)));
+        task.times_mut().arrival = self.time;
+        let task = Rc::new(RefCell::new(task));
+        self.emit_task(&task, None, Reason::Created);
+        self.new_queue.push(task);
         Ok(())
     }
     pub fn has_available_slots(&self) -> bool {
@@ -88,9 +364,10 @@ impl Scheduler {
     }
     pub fn block_task(&mut self, task: TaskRef) {
         assert_ne!(*task.borrow().state(), Blocked);
+        let from = TaskState::of(&task.borrow());
         task.borrow_mut().set_state(Blocked);
         task.borrow_mut().set_in_queue_time(self.time);
-        println!("Task {} blocked", task.borrow().pid());
+        self.emit_task(&task, Some(from), Reason::Blocked);
         if task.borrow().is_suspended() {
             self.blocked_suspend_queue.push(task);
         } else {
@@ -100,7 +377,7 @@ impl Scheduler {
     pub fn new_to_ready_task(&mut self, task: TaskRef) {
         assert_eq!(*task.borrow().state(), New);
         task.borrow_mut().set_state(Ready);
-        println!("Task {} New -> Ready", task.borrow().pid());
+        self.emit_task(&task, Some(TaskState::New), Reason::Admitted);
         self.task_queue.push(task);
     }
     // pub fn new_to_blocked_task(&mut self, task: TaskRef) {
@@ -109,23 +386,45 @@ impl Scheduler {
     //     println!("Task {} New -> Blocked", task.borrow().pid());
     //     self.blocked_queue.push(task);
     // }
-    pub fn ready_task(&mut self, task: TaskRef) {
+    pub fn ready_task(&mut self, task: TaskRef, reason: Reason, proc: Option<u32>) {
         assert_ne!(*task.borrow().state(), Ready);
+        let from = TaskState::of(&task.borrow());
         task.borrow_mut().set_state(Ready);
         task.borrow_mut().set_in_queue_time(self.time);
-        println!("Task Ready: {}", task.borrow().pid());
+        self.emit_task_on(&task, Some(from), reason, proc);
         if task.borrow().is_suspended() {
             self.ready_suspend_queue.push(task);
         } else {
             self.task_queue.push(task);
         }
     }
-    pub fn suspend_task(&mut self, task: TaskRef) {
+    pub fn suspend_task(&mut self, task: TaskRef) -> Result<(), TaskRef> {
         if task.borrow().is_suspended() {
             panic!("A task cannot suspend more than once!");
         }
+        let pid = task.borrow().pid();
+        let pages = self.reclaimable_size(&task);
+        let from = TaskState::of(&task.borrow());
+        if task.borrow().swap_ready_at() > self.time {
+            self.emit_task(&task, Some(from), Reason::SwapBusy);
+            return Err(task);
+        }
+        // write the pages out before giving the memory away
+        match self.swap.swap_out(pid, pages, self.time) {
+            Ok(done) => task.borrow_mut().set_swap_ready_at(done),
+            Err(_) => {
+                self.emit_task(&task, Some(from), Reason::SwapFull);
+                return Err(task);
+            }
+        }
         task.borrow_mut().suspend();
         task.borrow_mut().set_in_queue_time(self.time);
+        // a pending request is served together with the rest on unsuspend
+        let pending = task.borrow().pending_growth();
+        if let Some(size) = pending {
+            task.borrow_mut().grow(size);
+            task.borrow_mut().set_pending_growth(None);
+        }
         match task.borrow().state() {
             Ready => self.ready_suspend_queue.push(task.clone()),
             Blocked => self.blocked_suspend_queue.push(task.clone()),
@@ -133,19 +432,32 @@ impl Scheduler {
         }
         self.memory_manager.free(task.borrow().pid())
             .expect("free memory failed");
-        println!("Task Suspended: {}", task.borrow().pid());
+        self.memory_manager.detach_shared(task.borrow().pid());
+        self.frame_allocator.release(task.borrow().pid());
+        let swapped_until = task.borrow().swap_ready_at();
+        self.emit_task(&task, Some(from), Reason::Suspended { swapped_until });
+        Ok(())
     }
     pub fn unsuspend_task(&mut self, task: TaskRef) -> Result<(), TaskRef> {
         if !task.borrow().is_suspended() {
             panic!("A task cannot unsuspend more than once!");
         }
-        let (size, pid) = (task.borrow().memory_size(), task.borrow().pid());
+        let pid = task.borrow().pid();
+        let from = TaskState::of(&task.borrow());
+        // the pages are still being written out
+        if task.borrow().swap_ready_at() > self.time {
+            self.emit_task(&task, Some(from), Reason::SwapBusy);
+            return Err(task);
+        }
         // try to allocate memory
-        match self.memory_manager.allocate(size, pid) {
+        match self.allocate_task_memory(&task) {
             Ok(h) => {
+                let done = self.swap.swap_in(pid, self.time)
+                    .expect("swap in failed");
                 task.borrow_mut().unsuspend();
-                task.borrow_mut().set_memory_range(h);
-                println!("Task Unsuspended: {} with memory {:?}", task.borrow().pid(), task.borrow().memory_range());
+                task.borrow_mut().set_memory_ranges(h);
+                task.borrow_mut().set_swap_ready_at(done);
+                self.emit_task(&task, Some(from), Reason::Unsuspended { swapped_until: done });
                 match task.borrow().state() {
                     Ready => self.task_queue.push(task.clone()),
                     Blocked => self.blocked_queue.push(task.clone()),
@@ -154,28 +466,163 @@ impl Scheduler {
                 Ok(())
             }
             Err(_) => {
-                println!("failed to unsuspend task {}, out of memory", pid);
+                self.emit_task(&task, Some(from), Reason::OutOfMemory);
                 Err(task)
             }
         }
     }
-    pub fn terminate_task(&mut self, task: TaskRef) {
-        println!("==> {} finished. ", task.borrow().pid());
+    pub fn terminate_task(&mut self, task: TaskRef, proc: Option<u32>) {
+        let from = TaskState::of(&task.borrow());
         task.borrow_mut().set_state(Terminated);
+        let reason = if task.borrow().is_aborted() { Reason::Aborted } else { Reason::Finished };
+        self.emit_task_on(&task, Some(from), reason, proc);
+        // the task ran its last tick before this schedule
+        let time = self.time;
+        task.borrow_mut().times_mut().completion.get_or_insert(time - 1);
+        let metrics = {
+            let task = task.borrow();
+            TaskMetrics::new(task.pid(), task.times(), task.is_aborted())
+        };
+        self.finished.push(metrics);
         self.memory_manager.free(task.borrow().pid())
             .expect("free memory failed");
+        self.memory_manager.detach_shared(task.borrow().pid());
+        self.frame_allocator.forget(task.borrow().pid());
         let cond = self.pid_to_trigger.get(&task.borrow().pid()).unwrap().clone();
         cond.borrow_mut().set_ok();
     }
+    pub fn allocate_task_memory(&mut self, task: &TaskRef) -> Result<Vec<Hole>, MemoryError> {
+        // private regions first, then map the shared ones
+        let (sizes, shared, pid) = (task.borrow().region_sizes(), task.borrow().shared().clone(), task.borrow().pid());
+        let prefer = if self.numa_aware {
+            self.least_loaded_node().map(|n| self.numa.nodes()[n].range())
+        } else {
+            None
+        };
+        let holes = self.memory_manager.allocate_segments_near(&sizes, pid, prefer)?;
+        let home = holes.first().and_then(|h| self.numa.node_of_page(h.to_tuple().0));
+        task.borrow_mut().set_home_node(home);
+        for (shm_id, size) in shared {
+            if let Err(e) = self.memory_manager.attach_shared(shm_id, size, pid) {
+                self.memory_manager.free(pid).expect("free memory failed");
+                self.memory_manager.detach_shared(pid);
+                return Err(e);
+            }
+        }
+        self.memory_manager.record_waste(pid, task.borrow().wasted_bytes());
+        Ok(holes)
+    }
+    pub fn least_loaded_node(&self) -> Option<usize> {
+        // a node that can run the task locally, with the fewest tasks per processor, then the most free memory
+        let nodes = self.numa.nodes();
+        let mut homed = vec![0; nodes.len()];
+        for task in self.live_tasks() {
+            if let Some(node) = task.borrow().home_node() {
+                homed[node] += 1;
+            }
+        }
+        let procs = |n: usize| nodes[n].processors().len();
+        (0..nodes.len())
+            .filter(|n| procs(*n) > 0)
+            .min_by(|a, b| {
+                (homed[*a] * procs(*b)).cmp(&(homed[*b] * procs(*a)))
+                    .then_with(|| self.memory_manager.free_in(nodes[*b].range()).cmp(&self.memory_manager.free_in(nodes[*a].range())))
+            })
+    }
+    pub fn set_numa(&mut self, numa: NumaTopology, aware: bool) {
+        self.numa = numa;
+        self.numa_aware = aware;
+    }
+    pub fn numa(&self) -> &NumaTopology {
+        &self.numa
+    }
+    pub fn numa_stats(&self) -> NumaStats {
+        self.numa_stats
+    }
+    pub fn pick_task(&mut self, proc: usize) -> Option<TaskRef> {
+        // among the highest priority runnable tasks prefer one with local memory
+        if !self.numa_aware {
+            return self.task_queue.pop_max();
+        }
+        let top = self.task_queue.pop_max()?;
+        let priority = top.borrow().priority();
+        let mut same = vec![top];
+        while let Some(t) = self.task_queue.peek_max() {
+            if t.borrow().priority() != priority {
+                break;
+            }
+            same.push(self.task_queue.pop_max().unwrap());
+        }
+        let proc_id = self.processors[proc].proc_id();
+        let time = self.time;
+        let pos = same.iter()
+            .position(|t| {
+                let t = t.borrow();
+                t.is_runnable(time) && t.home_node().is_some_and(|n| self.numa.is_local(proc_id, n))
+            })
+            .or_else(|| same.iter().position(|t| !self.waits_for_home(t, proc)));
+        let task = pos.map(|pos| same.remove(pos));
+        self.task_queue.extend(same);
+        task
+    }
+    fn waits_for_home(&self, task: &TaskRef, proc: usize) -> bool {
+        // a task is left for an idle processor on its home node rather than run remotely
+        let home = match task.borrow().home_node() {
+            Some(home) => home,
+            None => return false,
+        };
+        self.numa_aware && !self.numa.is_local(self.processors[proc].proc_id(), home)
+            && self.processors.iter().any(|p| p.executing_task().is_none() && self.numa.is_local(p.proc_id(), home))
+    }
+    pub fn reclaimable_size(&self, task: &TaskRef) -> u32 {
+        // memory freed by swapping out `task`, regions still mapped by others stay
+        let pid = task.borrow().pid();
+        task.borrow().memory_size() + self.memory_manager.sole_shared_size(pid)
+    }
+    pub fn relieve_thrashing(&mut self) {
+        // suspend the lowest priority inactive tasks until the resident sets fit in frames
+        let mut no_frames = vec![];
+        while self.frame_allocator.is_thrashing() {
+            let victim = if !self.task_queue.is_empty() {
+                self.task_queue.pop_min().unwrap()
+            } else if !self.blocked_queue.is_empty() {
+                self.blocked_queue.pop_min().unwrap()
+            } else {
+                break;
+            };
+            // suspending a task without resident pages frees nothing
+            if self.frame_allocator.resident_size(victim.borrow().pid()) == 0 {
+                no_frames.push(victim);
+                continue;
+            }
+            let (demand, frames) = (self.frame_allocator.demand(), self.frame_allocator.total_frames());
+            self.events.emit(Event::new(self.time, Reason::Thrashing { demand, frames }));
+            if let Err(victim) = self.suspend_task(victim) {
+                no_frames.push(victim);
+                break;
+            }
+        }
+        for task in no_frames {
+            if *task.borrow().state() == Blocked {
+                self.blocked_queue.push(task);
+            } else {
+                self.task_queue.push(task);
+            }
+        }
+    }
     pub fn high_level_schedule(&mut self) {
+        self.relieve_thrashing();
         let mut out_of_mem = vec![];
         // ready, suspend <=> unsuspend
         // occupy all available slots
         while self.has_available_slots() && !self.ready_suspend_queue.is_empty(){
             let to_unsuspend_task = self.ready_suspend_queue.pop().unwrap();
-            match self.unsuspend_task(to_unsuspend_task) {
-                Err(task) => out_of_mem.push(task),
-                _ => (),
+            if !self.frame_allocator.can_admit(to_unsuspend_task.borrow().pid()) {
+                out_of_mem.push(to_unsuspend_task);
+                continue;
+            }
+            if let Err(task) = self.unsuspend_task(to_unsuspend_task) {
+                out_of_mem.push(task);
             }
         }
         self.ready_suspend_queue.extend(out_of_mem);
@@ -187,11 +634,16 @@ impl Scheduler {
             // higher priority
             if active_task.borrow().priority() < suspended_task.borrow().priority() {
                 // less mem
-                if active_task.borrow().memory_size() >= suspended_task.borrow().memory_size() {
+                if self.reclaimable_size(active_task) >= suspended_task.borrow().memory_size() {
                     let active_task = self.task_queue.pop_min().unwrap();
+                    if let Err(active_task) = self.suspend_task(active_task) {
+                        self.task_queue.push(active_task);
+                        break;
+                    }
                     let suspended_task = self.ready_suspend_queue.pop().unwrap();
-                    self.suspend_task(active_task);
-                    self.unsuspend_task(suspended_task).expect("unsuspend failed");
+                    if let Err(suspended_task) = self.unsuspend_task(suspended_task) {
+                        out_of_mem.push(suspended_task);
+                    }
                 } else {
                     out_of_mem.push(self.ready_suspend_queue.pop().unwrap());
                 }
@@ -207,9 +659,12 @@ impl Scheduler {
             // higher priority
             if active_task.borrow().priority() < new_task.borrow().priority() {
                 // less mem
-                if active_task.borrow().memory_size() >= new_task.borrow().memory_size() {
+                if self.reclaimable_size(active_task) >= new_task.borrow().memory_size() {
                     let active_task = self.task_queue.pop_min().unwrap();
-                    self.suspend_task(active_task);
+                    if let Err(active_task) = self.suspend_task(active_task) {
+                        self.task_queue.push(active_task);
+                        break;
+                    }
                 } else {
                     out_of_mem.push(self.new_queue.pop().unwrap());
                 }
@@ -222,22 +677,24 @@ impl Scheduler {
         // suspend blocked tasks if there are new tasks
         while !self.blocked_queue.is_empty() && !self.new_queue.is_empty() {
             let blocked_task = self.blocked_queue.pop_min().unwrap();
-            self.suspend_task(blocked_task);
+            if let Err(blocked_task) = self.suspend_task(blocked_task) {
+                self.blocked_queue.push(blocked_task);
+                break;
+            }
         }
     }
     pub fn mid_level_schedule(&mut self) {
         let mut out_of_mem = vec![];
-        while !self.new_queue.is_empty() && self.has_available_slots() {
+        while !self.new_queue.is_empty() && self.has_available_slots() && !self.frame_allocator.is_thrashing() {
             let task = self.new_queue.pop().unwrap();
             // allocate mem
-            let (size, pid) = (task.borrow().memory_size(), task.borrow().pid());
-            match self.memory_manager.allocate(size, pid) {
-                Ok(hole) => {
-                    task.borrow_mut().set_memory_range(hole);
+            match self.allocate_task_memory(&task) {
+                Ok(holes) => {
+                    task.borrow_mut().set_memory_ranges(holes);
                     self.new_to_ready_task(task);
                 }
                 Err(_) => {
-                    println!("No enough memory, {}", task.borrow().pid());
+                    self.emit_task(&task, Some(TaskState::New), Reason::OutOfMemory);
                     out_of_mem.push(task);
                 }
             }
@@ -246,18 +703,98 @@ impl Scheduler {
             self.new_queue.push(i.clone());
         }
     }
+    pub fn try_grow(&mut self, task: &TaskRef, size: u32) -> bool {
+        let pid = task.borrow().pid();
+        match self.memory_manager.allocate(size, pid) {
+            Ok(_) => {
+                task.borrow_mut().grow(size);
+                task.borrow_mut().set_memory_ranges(self.memory_manager.get_mem_usage()[&pid].clone());
+                let state = Some(TaskState::of(&task.borrow()));
+                self.emit_task(task, state, Reason::Grew { pages: size });
+                true
+            }
+            Err(_) => false,
+        }
+    }
+    pub fn handle_memory_requests(&mut self) {
+        for i in 0..self.processors.len() {
+            let task = match self.processors[i].executing_task() {
+                Some(task) => task,
+                None => continue,
+            };
+            if task.borrow().request_time() <= 0 {
+                continue;
+            }
+            let delta = match task.borrow_mut().take_memory_request() {
+                Some(delta) if delta != 0 => delta,
+                _ => continue,
+            };
+            let pid = task.borrow().pid();
+            if delta < 0 {
+                let released = task.borrow_mut().shrink(-delta as u32);
+                if released > 0 {
+                    self.memory_manager.shrink(released, pid)
+                        .expect("shrink memory failed");
+                    task.borrow_mut().set_memory_ranges(self.memory_manager.get_mem_usage()[&pid].clone());
+                }
+                let state = Some(TaskState::of(&task.borrow()));
+                self.emit_task(&task, state, Reason::Shrank { pages: released });
+                continue;
+            }
+            let size = delta as u32;
+            // not even every other task leaving would make room, waiting would block it forever
+            if size > self.memory_manager.largest_hole_for(pid) {
+                let state = Some(TaskState::of(&task.borrow()));
+                self.emit_task(&task, state, Reason::OutOfMemory);
+                task.borrow_mut().abort();
+                continue;
+            }
+            // make room by suspending lower priority ready tasks
+            while !self.try_grow(&task, size) {
+                let victim = match self.task_queue.peek_min() {
+                    Some(t) if t.borrow().priority() < task.borrow().priority() => self.task_queue.pop_min(),
+                    _ => None,
+                };
+                if let Some(victim) = victim {
+                    match self.suspend_task(victim) {
+                        Ok(()) => continue,
+                        Err(victim) => self.task_queue.push(victim),
+                    }
+                }
+                // nothing to take, wait for memory
+                let state = Some(TaskState::of(&task.borrow()));
+                self.emit_task(&task, state, Reason::WaitingForMemory { pages: size });
+                task.borrow_mut().set_pending_growth(Some(size));
+                let task = self.processors[i].release_task().unwrap();
+                self.block_task(task);
+                break;
+            }
+        }
+    }
     pub fn check_and_unblock(&mut self) {
         let mut not_ready = vec![];
         let mut ready = vec![];
+        let waiting: Vec<TaskRef> = self.blocked_queue.iter()
+            .filter(|t| t.borrow().pending_growth().is_some())
+            .cloned()
+            .collect();
+        for t in waiting {
+            // only retry once a hole fits, a failed retry is not another allocation failure
+            let size = t.borrow().pending_growth().unwrap();
+            if size <= self.memory_manager.largest_hole() && self.try_grow(&t, size) {
+                t.borrow_mut().set_pending_growth(None);
+            }
+        }
+        let time = self.time;
         for t in self.blocked_queue.drain() {
-            if t.borrow().is_cond_satisfied() {
+            if t.borrow().is_runnable(time) {
                 ready.push(t);
             } else {
                 not_ready.push(t);
             }
         }
         for t in self.blocked_suspend_queue.drain() {
-            if t.borrow().is_cond_satisfied() {
+            if t.borrow().is_runnable(time) {
                 ready.push(t);
             } else {
                 not_ready.push(t);
@@ -265,7 +802,7 @@ impl Scheduler {
         }
         for i in ready.iter() {
             if self.has_available_slots() {
-                self.ready_task(i.clone());
+                self.ready_task(i.clone(), Reason::Unblocked, None);
             } else {
                 not_ready.push(i.clone());
             }
@@ -278,43 +815,64 @@ impl Scheduler {
             }
         }
     }
-    pub fn low_level_schedule(&mut self, proc: usize) {
+    pub fn low_level_schedule(&mut self, proc_idx: usize) {
         let mut new_task = self.task_queue.peek_max();
-        while new_task.is_some() && !new_task.unwrap().borrow().is_cond_satisfied() {
+        while new_task.is_some() && !new_task.unwrap().borrow().is_runnable(self.time) {
             let temp = self.task_queue.pop_max().unwrap();
             self.block_task(temp);
             new_task = self.task_queue.peek_max();
         }
-        let proc = &mut self.processors[proc];
+        let proc = &mut self.processors[proc_idx];
         let mut preempt_flag = false;
         if let (Some(nt), Some(ot)) = (new_task, proc.executing_task()) {
             let (nt, ot) = (nt.clone(), ot.clone());
             if nt.borrow().priority() > ot.borrow().priority() {
                 preempt_flag = true;
-                println!("ready to preempt {} -> {}", nt.borrow().pid(), ot.borrow().pid());
             }
         }
         if preempt_flag || proc.is_task_finished() {
-            let new_task = self.task_queue.pop_max();
+            let new_task = self.pick_task(proc_idx);
+            let proc_id = self.processors[proc_idx].proc_id();
+            // with nothing else to run, a task at the end of its slice still moves to an idle home processor
+            let go_home = new_task.is_none() && self.processors[proc_idx].is_task_finished()
+                && self.processors[proc_idx].executing_task()
+                .is_some_and(|t| t.borrow().request_time() > 0 && self.waits_for_home(&t, proc_idx));
             if let Some(temp) = &new_task {
+                let from = TaskState::of(&temp.borrow());
                 temp.borrow_mut().set_sch_time(self.time_slice as i32);
                 temp.borrow_mut().set_state(Running);
+                let start = self.time - 1;
+                temp.borrow_mut().times_mut().first_run.get_or_insert(start);
+                let (pid, time) = (temp.borrow().pid(), self.time);
+                self.events.emit(Event::new(time, Reason::Dispatched).task(pid, Some(from), Some(TaskState::Running)).on(proc_id));
             }
-            let old_task = proc.turn_to_task(new_task);
+            let by = new_task.as_ref().map(|t| t.borrow().pid());
+            let old_task = if go_home {
+                self.processors[proc_idx].release_task()
+            } else {
+                self.processors[proc_idx].turn_to_task(new_task)
+            };
             if let Some(task) = old_task {
                 if task.borrow_mut().request_time() <= 0 {
-                    self.terminate_task(task);
+                    self.terminate_task(task, Some(proc_id));
                 } else {
                     // decrement priority
                     let pri = task.borrow().priority();
                     task.borrow_mut().set_priority(pri - 1);
-                    self.ready_task(task);
+                    let reason = match by {
+                        Some(by) if preempt_flag => {
+                            self.timeline.mark_preemption(self.time, proc_id);
+                            Reason::Preempted { by }
+                        }
+                        _ => Reason::SliceExpired,
+                    };
+                    self.ready_task(task, reason, Some(proc_id));
                 }
             }
         }
     }
     pub fn schedule(&mut self) {
-        println!("time {}: ", self.time);
+        self.events.emit(Event::new(self.time, Reason::Tick));
         self.check_and_unblock();
         // suspend <=> unsuspend
         self.high_level_schedule();
@@ -324,32 +882,159 @@ impl Scheduler {
         for proc in 0..self.processors.len() {
             self.low_level_schedule(proc);
         }
+        // idle processors take the tasks left for them on their home node
+        if self.numa_aware {
+            for proc in 0..self.processors.len() {
+                if self.processors[proc].executing_task().is_none() {
+                    self.low_level_schedule(proc);
+                }
+            }
+        }
     }
     pub fn advance_time(&mut self) {
         self.time += 1;
         self.schedule();
+        for task in self.new_queue.iter().chain(self.task_queue.iter()).chain(self.ready_suspend_queue.iter()) {
+            task.borrow_mut().times_mut().waiting += 1;
+        }
+        for task in self.blocked_queue.iter().chain(self.blocked_suspend_queue.iter()) {
+            task.borrow_mut().times_mut().blocked += 1;
+        }
         // run task
+        let mut refs = vec![];
         for proc in self.processors.iter_mut() {
-            proc.run_task();
+            if proc.executing_task().is_some() {
+                self.busy_ticks += 1;
+            }
+            if let Some(r) = proc.run_task(self.time, &mut self.events) {
+                refs.push(r);
+            }
+            // every tick on a processor touches the task's memory
+            if let Some(task) = proc.executing_task() {
+                let home = task.borrow().home_node();
+                if let Some(node) = home {
+                    let cost = self.numa.access_cost(proc.proc_id(), node);
+                    if self.numa.is_local(proc.proc_id(), node) {
+                        self.numa_stats.local_accesses += 1;
+                    } else {
+                        self.numa_stats.remote_accesses += 1;
+                    }
+                    self.numa_stats.cost += cost as u64;
+                    task.borrow_mut().add_numa_cost(cost, self.numa.local_cost());
+                }
+            }
         }
+        let running = self.get_executing_tasks();
+        self.timeline.push_tick(running);
+        for (pid, access) in refs {
+            match access {
+                Access::Page(page) => {
+                    if self.frame_allocator.reference(pid, page) {
+                        let state = Some(TaskState::Running);
+                        self.events.emit(Event::new(self.time, Reason::PageFault { page }).task(pid, state, state));
+                    }
+                }
+                Access::Fault(r) => {
+                    let state = Some(TaskState::Running);
+                    let reason = Reason::SegmentationFault { segment: r.segment, offset: r.offset };
+                    self.events.emit(Event::new(self.time, reason).task(pid, state, state));
+                    self.abort_task(pid);
+                }
+            }
+        }
+        self.handle_memory_requests();
+        // tasks out of time (or aborted) complete at the end of this tick
+        for proc in self.processors.iter() {
+            if let Some(task) = proc.executing_task() {
+                if task.borrow().request_time() <= 0 {
+                    let time = self.time;
+                    task.borrow_mut().times_mut().completion.get_or_insert(time);
+                }
+            }
+        }
+        self.memory_history.push(self.memory_manager.metrics());
         // self.memory_manager.print();
     }
+    pub fn abort_task(&mut self, pid: u32) {
+        // a running task with no time left is terminated at the next schedule
+        for proc in self.processors.iter() {
+            if let Some(task) = proc.executing_task() {
+                if task.borrow().pid() == pid {
+                    task.borrow_mut().abort();
+                }
+            }
+        }
+    }
     pub fn memory_manager(&self) -> &MemoryManager {
         &self.memory_manager
     }
-    pub fn get_executing_tasks(&self) -> [Option<u32>; 2] {
-        let mut temp = [None, None];
-        for i in 0..self.processors.len() {
-            let proc = &self.processors[i];
-            let t = proc.executing_task().clone();
-            let t = if let Some(t) = t {
-                Some(t.borrow().pid())
-            } else {
-                None
-            };
-            temp[i] = t;
-        }
-        temp
+    pub fn memory_history(&self) -> &Vec<MemoryMetrics> {
+        // one sample per tick
+        &self.memory_history
+    }
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+    pub fn swap(&self) -> &SwapDevice {
+        &self.swap
+    }
+    pub fn frame_allocator(&self) -> &FrameAllocator {
+        &self.frame_allocator
+    }
+    pub fn tlb_stats(&self) -> Vec<TlbStats> {
+        self.processors.iter().map(|p| p.tlb().stats()).collect()
+    }
+    pub fn live_tasks(&self) -> Vec<TaskRef> {
+        // every task that has not terminated, in no particular order
+        let mut tasks: Vec<TaskRef> = self.new_queue.iter()
+            .chain(self.task_queue.iter())
+            .chain(self.blocked_queue.iter())
+            .chain(self.blocked_suspend_queue.iter())
+            .chain(self.ready_suspend_queue.iter())
+            .cloned()
+            .collect();
+        tasks.extend(self.processors.iter().filter_map(|p| p.executing_task()));
+        tasks
+    }
+    pub fn queues(&self) -> Vec<(&'static str, Vec<TaskRef>)> {
+        // running tasks by processor, then every queue with the task picked next first
+        let sorted = |tasks: Vec<&TaskRef>| {
+            let mut tasks: Vec<TaskRef> = tasks.into_iter().cloned().collect();
+            tasks.sort_by(|a, b| b.cmp(a));
+            tasks
+        };
+        vec![
+            ("running", self.processors.iter().filter_map(|p| p.executing_task()).collect()),
+            ("new", sorted(self.new_queue.iter().collect())),
+            ("ready", sorted(self.task_queue.iter().collect())),
+            ("blocked", sorted(self.blocked_queue.iter().collect())),
+            ("ready suspended", sorted(self.ready_suspend_queue.iter().collect())),
+            ("blocked suspended", sorted(self.blocked_suspend_queue.iter().collect())),
+        ]
+    }
+    pub fn task_metrics(&self) -> Vec<TaskMetrics> {
+        // terminated and live tasks by pid
+        let mut metrics = self.finished.clone();
+        metrics.extend(self.live_tasks().iter().map(|t| {
+            let t = t.borrow();
+            TaskMetrics::new(t.pid(), t.times(), t.is_aborted())
+        }));
+        metrics.sort_by_key(|m| m.pid);
+        metrics
+    }
+    pub fn aggregate_metrics(&self) -> AggregateMetrics {
+        AggregateMetrics::new(&self.task_metrics(), self.time, self.busy_ticks, self.processors.len())
+    }
+    pub fn is_idle(&self) -> bool {
+        // no task is waiting, blocked or running
+        self.new_queue.is_empty() && self.task_queue.is_empty() && self.blocked_queue.is_empty()
+            && self.blocked_suspend_queue.is_empty() && self.ready_suspend_queue.is_empty()
+            && self.processors.iter().all(|p| p.executing_task().is_none())
+    }
+    pub fn get_executing_tasks(&self) -> Vec<Option<u32>> {
+        self.processors.iter()
+            .map(|proc| proc.executing_task().map(|t| t.borrow().pid()))
+            .collect()
     }
     pub fn time(&self) -> i32 {
         self.time
@@ -1,8 +1,10 @@
 use std::cmp::{Ordering, Reverse};
 use crate::memory::hole::*;
 use crate::condition::*;
+use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ProcessState {
     New,
     Ready,
@@ -11,7 +13,56 @@ pub enum ProcessState {
     Blocked,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentKind {
+    Code,
+    Data,
+    Stack,
+    Heap,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Reference {
+    pub segment: usize,
+    pub offset: u32,
+}
+
+impl From<u32> for Reference {
+    fn from(page: u32) -> Self {
+        // flat tasks only have segment 0
+        Reference {
+            segment: 0,
+            offset: page,
+        }
+    }
+}
+
+// times are tick boundaries, tick t runs from t - 1 to t
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TaskTimes {
+    pub arrival: i32,
+    pub burst: i32,
+    pub first_run: Option<i32>,
+    pub completion: Option<i32>,
+    // ticks spent waiting for admission or a processor, and in the blocked queues
+    pub waiting: i32,
+    pub blocked: i32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Access {
+    Page(u32),
+    Fault(Reference),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pid: u32,
     request_time: i32,
@@ -20,9 +71,34 @@ pub struct Task {
     state: ProcessState,
     in_queue_time: i32,
     memory_size: u32,
-    memory_range: Option<Hole>,
+    // bytes of the last page the task asked for but does not use
+    wasted_bytes: u64,
+    memory_ranges: Vec<Hole>,
+    segments: Vec<Segment>,
+    // (shared memory id, size) pairs the task maps
+    shared: Vec<(u32, u32)>,
+    // regions added at runtime, on top of the initial footprint
+    grown: Vec<u32>,
+    pending_growth: Option<u32>,
+    // (run time, size delta) pairs, a positive delta asks for memory
+    memory_script: Vec<(i32, i32)>,
+    run_time: i32,
+    // time the last swap transfer of the task completes
+    swap_ready_at: i32,
+    // numa node holding the task's memory
+    home_node: Option<usize>,
+    numa_cost: u64,
+    // remote access cost not yet turned into a tick of work
+    numa_stall: u32,
+    // shared with the task waited for, snapshots store its pid instead
+    #[serde(skip)]
     cond: Option<ConditionRef>,
-    is_suspended: bool
+    is_suspended: bool,
+    // ended early by a fault instead of running its burst
+    aborted: bool,
+    references: Vec<Reference>,
+    ref_pos: usize,
+    times: TaskTimes,
 }
 
 impl Task {
@@ -35,11 +111,35 @@ impl Task {
             in_queue_time: 0,
             sch_time: 0x3f3f3f3f,
             memory_size,
-            memory_range: None,
+            wasted_bytes: 0,
+            memory_ranges: vec![],
+            segments: vec![],
+            shared: vec![],
+            grown: vec![],
+            pending_growth: None,
+            memory_script: vec![],
+            run_time: 0,
+            swap_ready_at: 0,
+            home_node: None,
+            numa_cost: 0,
+            numa_stall: 0,
             cond: None,
             is_suspended: false,
+            aborted: false,
+            references: vec![],
+            ref_pos: 0,
+            times: TaskTimes {
+                burst: request_time,
+                ..TaskTimes::default()
+            },
         }
     }
+    pub fn times(&self) -> &TaskTimes {
+        &self.times
+    }
+    pub fn times_mut(&mut self) -> &mut TaskTimes {
+        &mut self.times
+    }
     pub fn pid(&self) -> u32 {
         self.pid
     }
@@ -70,15 +170,93 @@ impl Task {
     pub fn decrement_time(&mut self, t: i32) {
         self.request_time -= t;
         self.sch_time -= t;
+        self.run_time += t;
+    }
+    pub fn run_time(&self) -> i32 {
+        self.run_time
+    }
+    pub fn memory_ranges(&self) -> &Vec<Hole> {
+        &self.memory_ranges
+    }
+    pub fn set_memory_ranges(&mut self, ranges: Vec<Hole>) {
+        self.memory_ranges = ranges;
+    }
+    pub fn segments(&self) -> &Vec<Segment> {
+        &self.segments
+    }
+    pub fn set_segments(&mut self, segments: Vec<Segment>) {
+        self.memory_size = segments.iter().map(|s| s.size).sum();
+        self.segments = segments;
+    }
+    pub fn segment_sizes(&self) -> Vec<u32> {
+        // a task without memory has no segment at all
+        if self.segments.is_empty() && self.memory_size == 0 {
+            vec![]
+        } else if self.segments.is_empty() {
+            vec![self.memory_size]
+        } else {
+            self.segments.iter().map(|s| s.size).collect()
+        }
+    }
+    pub fn shared(&self) -> &Vec<(u32, u32)> {
+        &self.shared
+    }
+    pub fn set_shared(&mut self, shared: Vec<(u32, u32)>) {
+        self.shared = shared;
+    }
+    pub fn region_sizes(&self) -> Vec<u32> {
+        let mut sizes = self.segment_sizes();
+        sizes.extend(self.grown.iter());
+        sizes
     }
-    pub fn memory_range(&self) -> Option<Hole> {
-        self.memory_range
+    pub fn memory_script(&self) -> &Vec<(i32, i32)> {
+        &self.memory_script
     }
-    pub fn set_memory_range(&mut self, h: Hole) {
-        self.memory_range = Some(h);
+    pub fn set_memory_script(&mut self, script: Vec<(i32, i32)>) {
+        self.memory_script = script;
+    }
+    pub fn take_memory_request(&mut self) -> Option<i32> {
+        // sum of the script entries due at the current run time
+        let mut delta = None;
+        for (at, d) in self.memory_script.iter() {
+            if *at == self.run_time {
+                delta = Some(delta.unwrap_or(0) + *d);
+            }
+        }
+        delta
+    }
+    pub fn grow(&mut self, size: u32) {
+        self.grown.push(size);
+    }
+    pub fn shrink(&mut self, size: u32) -> u32 {
+        // only memory grown at runtime can be released, return the amount released
+        let mut left = size;
+        while left > 0 {
+            match self.grown.pop() {
+                None => break,
+                Some(last) if last > left => {
+                    self.grown.push(last - left);
+                    left = 0;
+                }
+                Some(last) => left -= last,
+            }
+        }
+        size - left
+    }
+    pub fn pending_growth(&self) -> Option<u32> {
+        self.pending_growth
+    }
+    pub fn set_pending_growth(&mut self, size: Option<u32>) {
+        self.pending_growth = size;
     }
     pub fn memory_size(&self) -> u32 {
-        self.memory_size
+        self.memory_size + self.grown.iter().sum::<u32>()
+    }
+    pub fn wasted_bytes(&self) -> u64 {
+        self.wasted_bytes
+    }
+    pub fn set_wasted_bytes(&mut self, bytes: u64) {
+        self.wasted_bytes = bytes;
     }
     pub fn sch_time(&self) -> i32 {
         self.sch_time
@@ -100,6 +278,34 @@ impl Task {
             }
         }
     }
+    pub fn is_runnable(&self, time: i32) -> bool {
+        self.is_cond_satisfied() && self.pending_growth.is_none() && self.swap_ready_at <= time
+    }
+    pub fn home_node(&self) -> Option<usize> {
+        self.home_node
+    }
+    pub fn set_home_node(&mut self, node: Option<usize>) {
+        self.home_node = node;
+    }
+    pub fn numa_cost(&self) -> u64 {
+        self.numa_cost
+    }
+    pub fn add_numa_cost(&mut self, cost: u32, local_cost: u32) {
+        // a tick at `cost` only gets `local_cost / cost` of a tick of work done,
+        // the rest is given back to the remaining time
+        self.numa_cost += cost as u64;
+        if cost > local_cost {
+            self.numa_stall += cost - local_cost;
+            self.request_time += (self.numa_stall / cost) as i32;
+            self.numa_stall %= cost;
+        }
+    }
+    pub fn swap_ready_at(&self) -> i32 {
+        self.swap_ready_at
+    }
+    pub fn set_swap_ready_at(&mut self, time: i32) {
+        self.swap_ready_at = time;
+    }
     pub fn is_suspended(&self) -> bool {
         self.is_suspended
     }
@@ -109,6 +315,37 @@ impl Task {
     pub fn unsuspend(&mut self) {
         self.is_suspended = false;
     }
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+    pub fn abort(&mut self) {
+        // the task has no time left and terminates at the next schedule
+        self.aborted = true;
+        self.request_time = 0;
+    }
+    pub fn references(&self) -> &Vec<Reference> {
+        &self.references
+    }
+    pub fn set_references(&mut self, references: Vec<Reference>) {
+        self.references = references;
+        self.ref_pos = 0;
+    }
+    pub fn next_reference(&mut self) -> Option<Access> {
+        // the reference string repeats until the task finishes
+        if self.references.is_empty() {
+            return None;
+        }
+        let r = self.references[self.ref_pos % self.references.len()];
+        self.ref_pos += 1;
+        // segments are laid out one after another in the logical address space
+        let sizes = self.segment_sizes();
+        if r.segment >= sizes.len() || r.offset >= sizes[r.segment] {
+            Some(Access::Fault(r))
+        } else {
+            let base: u32 = sizes[..r.segment].iter().sum();
+            Some(Access::Page(base + r.offset))
+        }
+    }
 }
 
 impl PartialEq for Task {
@@ -119,7 +356,7 @@ impl PartialEq for Task {
 
 impl PartialOrd for Task {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        (self.priority, Reverse(self.in_queue_time)).partial_cmp(&(other.priority, Reverse(other.in_queue_time)))
+        Some(self.cmp(other))
     }
 }
 
@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+
+// a task running on a processor for consecutive ticks
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct Run {
+    pub pid: u32,
+    pub processor: u32,
+    // tick boundaries, the run covers ticks start + 1 ..= end
+    pub start: i32,
+    pub end: i32,
+    // the run began by preempting the previous task
+    pub preempted: bool,
+    // another task ran last on this processor, idle gaps do not count
+    pub context_switch: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timeline {
+    // pid that ran on every processor, one entry per tick
+    ticks: Vec<Vec<Option<u32>>>,
+    // (tick, processor) where the running task was preempted
+    preemptions: HashSet<(i32, u32)>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push_tick(&mut self, running: Vec<Option<u32>>) {
+        self.ticks.push(running);
+    }
+    pub fn mark_preemption(&mut self, tick: i32, processor: u32) {
+        self.preemptions.insert((tick, processor));
+    }
+    pub fn tail(&self, from: usize) -> Timeline {
+        // the ticks after the first `from`
+        Timeline {
+            ticks: self.ticks[from..].to_vec(),
+            preemptions: self.preemptions.iter().filter(|(tick, _)| *tick as usize > from).copied().collect(),
+        }
+    }
+    pub fn prefix(&self, len: usize) -> Timeline {
+        Timeline {
+            ticks: self.ticks[..len].to_vec(),
+            preemptions: self.preemptions.iter().filter(|(tick, _)| *tick as usize <= len).copied().collect(),
+        }
+    }
+    pub fn append(&mut self, tail: Timeline) {
+        self.ticks.extend(tail.ticks);
+        self.preemptions.extend(tail.preemptions);
+    }
+    pub fn truncate(&mut self, len: usize) {
+        self.ticks.truncate(len);
+        self.preemptions.retain(|(tick, _)| *tick as usize <= len);
+    }
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+    pub fn processor_count(&self) -> usize {
+        self.ticks.iter().map(|t| t.len()).max().unwrap_or(0)
+    }
+    pub fn pid_at(&self, tick: i32, processor: u32) -> Option<u32> {
+        // ticks start at 1
+        if tick < 1 {
+            return None;
+        }
+        *self.ticks.get(tick as usize - 1)?.get(processor as usize)?
+    }
+    pub fn is_preemption(&self, tick: i32, processor: u32) -> bool {
+        self.preemptions.contains(&(tick, processor))
+    }
+    pub fn runs(&self) -> Vec<Run> {
+        // maximal spans of the same pid, by processor then time
+        let mut runs: Vec<Run> = vec![];
+        for processor in 0..self.processor_count() as u32 {
+            let mut last_pid = None;
+            for tick in 1..=self.ticks.len() as i32 {
+                let pid = match self.pid_at(tick, processor) {
+                    None => continue,
+                    Some(pid) => pid,
+                };
+                let preempted = self.is_preemption(tick, processor);
+                match runs.last_mut() {
+                    Some(run) if run.processor == processor && run.pid == pid && run.end == tick - 1
+                        && !preempted => run.end = tick,
+                    _ => runs.push(Run {
+                        pid,
+                        processor,
+                        start: tick - 1,
+                        end: tick,
+                        preempted,
+                        context_switch: last_pid.is_some_and(|last| last != pid),
+                    }),
+                }
+                last_pid = Some(pid);
+            }
+        }
+        runs
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::memory::size::MemSize;
+use crate::task::{Segment, Reference};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WorkloadFormat {
+    Toml,
+    Json,
+}
+
+impl WorkloadFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        // json by extension, toml otherwise
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("json") => WorkloadFormat::Json,
+            _ => WorkloadFormat::Toml,
+        }
+    }
+}
+
+fn default_priority() -> i32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSpec {
+    // name of the task within the workload, used by `after`
+    pub id: u32,
+    // ticks after the workload is loaded
+    #[serde(default)]
+    pub arrival: i32,
+    pub burst: i32,
+    #[serde(default = "default_priority")]
+    pub priority: i32,
+    pub memory: MemSize,
+    // id of the task this one waits for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<u32>,
+    // (shared memory id, size) pairs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shared: Vec<(u32, u32)>,
+    // (run time, size delta) pairs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub script: Vec<(i32, i32)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segment_references: Vec<Reference>,
+}
+
+impl TaskSpec {
+    pub fn new(id: u32, arrival: i32, burst: i32, priority: i32, memory: MemSize, after: Option<u32>) -> Self {
+        TaskSpec {
+            id,
+            arrival,
+            burst,
+            priority,
+            memory,
+            after,
+            references: vec![],
+            shared: vec![],
+            script: vec![],
+            segments: vec![],
+            segment_references: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Workload {
+    #[serde(default)]
+    pub tasks: Vec<TaskSpec>,
+}
+
+impl Workload {
+    pub fn parse(s: &str, format: WorkloadFormat) -> Result<Self, String> {
+        let workload: Workload = match format {
+            WorkloadFormat::Toml => toml::from_str(s).map_err(|e| format!("invalid workload: {}", e))?,
+            WorkloadFormat::Json => serde_json::from_str(s).map_err(|e| format!("invalid workload: {}", e))?,
+        };
+        workload.validate()?;
+        Ok(workload)
+    }
+    pub fn to_text(&self, format: WorkloadFormat) -> String {
+        match format {
+            WorkloadFormat::Toml => toml::to_string(&toml::Value::try_from(self).unwrap()).unwrap(),
+            WorkloadFormat::Json => serde_json::to_string_pretty(self).unwrap(),
+        }
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let s = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("unable to read {}: {}", path.as_ref().display(), e))?;
+        Self::parse(&s, WorkloadFormat::from_path(path))
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_text(WorkloadFormat::from_path(path.as_ref())))
+            .map_err(|e| format!("unable to write {}: {}", path.as_ref().display(), e))
+    }
+    pub fn sorted(&self) -> Vec<TaskSpec> {
+        // arrival order, ties keep the file order
+        let mut tasks = self.tasks.clone();
+        tasks.sort_by_key(|t| t.arrival);
+        tasks
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        // ids are unique and a task only waits for one that arrived before it
+        let mut seen = vec![];
+        let mut shared = HashMap::new();
+        for task in self.sorted() {
+            if seen.contains(&task.id) {
+                return Err(format!("duplicate task id {}", task.id));
+            }
+            if task.burst <= 0 || task.arrival < 0 {
+                return Err(format!("task {} needs a positive burst and a non-negative arrival", task.id));
+            }
+            if task.segments.iter().any(|s| s.size == 0) {
+                return Err(format!("task {} has an empty segment", task.id));
+            }
+            for (shm_id, size) in task.shared.iter() {
+                if *size == 0 {
+                    return Err(format!("task {} maps an empty shared memory {}", task.id, shm_id));
+                }
+                if *shared.entry(*shm_id).or_insert(*size) != *size {
+                    return Err(format!("task {} maps shared memory {} with another size", task.id, shm_id));
+                }
+            }
+            if task.script.iter().any(|(at, _)| *at <= 0) {
+                return Err(format!("task {} has a memory request before it ever runs", task.id));
+            }
+            if let Some(after) = task.after {
+                if !seen.contains(&after) {
+                    return Err(format!("task {} waits for task {}, which does not arrive before it", task.id, after));
+                }
+            }
+            seen.push(task.id);
+        }
+        Ok(())
+    }
+}